@@ -21,19 +21,39 @@ Register-ArgumentCompleter -Native -CommandName 'tex-fmt' -ScriptBlock {
 
     $completions = @(switch ($command) {
         'tex-fmt' {
+            [CompletionResult]::new('--diff', '--diff', [CompletionResultType]::ParameterName, 'Print a diff of formatting changes, do not modify files')
             [CompletionResult]::new('-l', '-l', [CompletionResultType]::ParameterName, 'Line length for wrapping [default: 80]')
             [CompletionResult]::new('--wraplen', '--wraplen', [CompletionResultType]::ParameterName, 'Line length for wrapping [default: 80]')
+            [CompletionResult]::new('--wrap-mode', '--wrap-mode', [CompletionResultType]::ParameterName, 'Where to break lines when wrapping [default: width]')
+            [CompletionResult]::new('--bib-field-order', '--bib-field-order', [CompletionResultType]::ParameterName, 'Order of fields in bibliography entries, separated by commas')
             [CompletionResult]::new('-t', '-t', [CompletionResultType]::ParameterName, 'Number of characters to use as tab size [default: 2]')
             [CompletionResult]::new('--tabsize', '--tabsize', [CompletionResultType]::ParameterName, 'Number of characters to use as tab size [default: 2]')
+            [CompletionResult]::new('--line-ending', '--line-ending', [CompletionResultType]::ParameterName, 'Line ending for formatted files [default: auto]')
+            [CompletionResult]::new('--verbatims', '--verbatims', [CompletionResultType]::ParameterName, 'Extra verbatim environments, separated by commas')
+            [CompletionResult]::new('--lists', '--lists', [CompletionResultType]::ParameterName, 'Extra list environments, separated by commas')
+            [CompletionResult]::new('--no-indent-envs', '--no-indent-envs', [CompletionResultType]::ParameterName, 'Extra environments which are not indented, separated by commas')
+            [CompletionResult]::new('--lines', '--lines', [CompletionResultType]::ParameterName, 'Only format lines in this range, can be repeated')
+            [CompletionResult]::new('--since', '--since', [CompletionResultType]::ParameterName, 'Only format lines changed since a git revision')
+            [CompletionResult]::new('--backup', '--backup', [CompletionResultType]::ParameterName, 'Keep a backup of each modified file [default: .bak]')
+            [CompletionResult]::new('-j', '-j', [CompletionResultType]::ParameterName, 'Number of files to format in parallel [default: number of CPUs]')
+            [CompletionResult]::new('--jobs', '--jobs', [CompletionResultType]::ParameterName, 'Number of files to format in parallel [default: number of CPUs]')
             [CompletionResult]::new('--config', '--config', [CompletionResultType]::ParameterName, 'Path to configuration file')
+            [CompletionResult]::new('--output-format', '--output-format', [CompletionResultType]::ParameterName, 'Format used to display log messages [default: human]')
             [CompletionResult]::new('--completion', '--completion', [CompletionResultType]::ParameterName, 'Generate shell completion script')
             [CompletionResult]::new('-c', '-c', [CompletionResultType]::ParameterName, 'Check formatting, do not modify files')
             [CompletionResult]::new('--check', '--check', [CompletionResultType]::ParameterName, 'Check formatting, do not modify files')
+            [CompletionResult]::new('--check-idempotent', '--check-idempotent', [CompletionResultType]::ParameterName, 'Check that formatting twice makes no further changes')
             [CompletionResult]::new('-p', '-p', [CompletionResultType]::ParameterName, 'Print to stdout, do not modify files')
             [CompletionResult]::new('--print', '--print', [CompletionResultType]::ParameterName, 'Print to stdout, do not modify files')
+            [CompletionResult]::new('--verify', '--verify', [CompletionResultType]::ParameterName, 'Check that formatting only changes whitespace')
             [CompletionResult]::new('-n', '-n', [CompletionResultType]::ParameterName, 'Do not wrap long lines')
             [CompletionResult]::new('--nowrap', '--nowrap', [CompletionResultType]::ParameterName, 'Do not wrap long lines')
+            [CompletionResult]::new('--join-sentences', '--join-sentences', [CompletionResultType]::ParameterName, 'Join lines split in the middle of a sentence')
+            [CompletionResult]::new('--brace-wrap', '--brace-wrap', [CompletionResultType]::ParameterName, 'Avoid wrapping inside braces and inline math')
+            [CompletionResult]::new('--align-tables', '--align-tables', [CompletionResultType]::ParameterName, 'Align & columns in tables and aligned environments')
+            [CompletionResult]::new('--bib-sort', '--bib-sort', [CompletionResultType]::ParameterName, 'Sort bibliography entries by key')
             [CompletionResult]::new('--usetabs', '--usetabs', [CompletionResultType]::ParameterName, 'Use tabs instead of spaces for indentation')
+            [CompletionResult]::new('--changed', '--changed', [CompletionResultType]::ParameterName, 'Only format lines changed since the last git commit')
             [CompletionResult]::new('-s', '-s', [CompletionResultType]::ParameterName, 'Process stdin as a single file, output to stdout')
             [CompletionResult]::new('--stdin', '--stdin', [CompletionResultType]::ParameterName, 'Process stdin as a single file, output to stdout')
             [CompletionResult]::new('--noconfig', '--noconfig', [CompletionResultType]::ParameterName, 'Do not read any config file')
@@ -48,6 +68,24 @@ Register-ArgumentCompleter -Native -CommandName 'tex-fmt' -ScriptBlock {
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
             [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'Print version')
+            [CompletionResult]::new('lsp', 'lsp', [CompletionResultType]::ParameterValue, 'Run as a language server over stdin and stdout')
+            [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
+            break
+        }
+        'tex-fmt;lsp' {
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            break
+        }
+        'tex-fmt;help' {
+            [CompletionResult]::new('lsp', 'lsp', [CompletionResultType]::ParameterValue, 'Run as a language server over stdin and stdout')
+            [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
+            break
+        }
+        'tex-fmt;help;lsp' {
+            break
+        }
+        'tex-fmt;help;help' {
             break
         }
     })
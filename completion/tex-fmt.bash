@@ -12,6 +12,18 @@ _tex-fmt() {
             ",$1")
                 cmd="tex__fmt"
                 ;;
+            tex__fmt,help)
+                cmd="tex__fmt__help"
+                ;;
+            tex__fmt,lsp)
+                cmd="tex__fmt__lsp"
+                ;;
+            tex__fmt__help,help)
+                cmd="tex__fmt__help__help"
+                ;;
+            tex__fmt__help,lsp)
+                cmd="tex__fmt__help__lsp"
+                ;;
             *)
                 ;;
         esac
@@ -19,12 +31,16 @@ _tex-fmt() {
 
     case "${cmd}" in
         tex__fmt)
-            opts="-c -p -n -l -t -s -v -q -h -V --check --print --nowrap --wraplen --tabsize --usetabs --stdin --config --noconfig --verbose --quiet --trace --completion --man --args --help --version [files]..."
+            opts="-c -p -n -l -t -s -j -v -q -h -V --check --check-idempotent --diff --print --verify --nowrap --wraplen --wrap-mode --join-sentences --brace-wrap --align-tables --bib-sort --bib-field-order --tabsize --usetabs --line-ending --verbatims --lists --no-indent-envs --lines --changed --since --backup --stdin --jobs --config --noconfig --verbose --quiet --trace --output-format --completion --man --args --help --version [files]... lsp help"
             if [[ ${cur} == -* || ${COMP_CWORD} -eq 1 ]] ; then
                 COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
                 return 0
             fi
             case "${prev}" in
+                --diff)
+                    COMPREPLY=($(compgen -W "color plain" -- "${cur}"))
+                    return 0
+                    ;;
                 --wraplen)
                     COMPREPLY=($(compgen -f "${cur}"))
                     return 0
@@ -33,6 +49,14 @@ _tex-fmt() {
                     COMPREPLY=($(compgen -f "${cur}"))
                     return 0
                     ;;
+                --wrap-mode)
+                    COMPREPLY=($(compgen -W "width sentence reflow" -- "${cur}"))
+                    return 0
+                    ;;
+                --bib-field-order)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
                 --tabsize)
                     COMPREPLY=($(compgen -f "${cur}"))
                     return 0
@@ -41,10 +65,50 @@ _tex-fmt() {
                     COMPREPLY=($(compgen -f "${cur}"))
                     return 0
                     ;;
+                --line-ending)
+                    COMPREPLY=($(compgen -W "auto lf crlf native" -- "${cur}"))
+                    return 0
+                    ;;
+                --verbatims)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --lists)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --no-indent-envs)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --lines)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --since)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --backup)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                --jobs)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
+                -j)
+                    COMPREPLY=($(compgen -f "${cur}"))
+                    return 0
+                    ;;
                 --config)
                     COMPREPLY=($(compgen -f "${cur}"))
                     return 0
                     ;;
+                --output-format)
+                    COMPREPLY=($(compgen -W "human json json-lines github sarif" -- "${cur}"))
+                    return 0
+                    ;;
                 --completion)
                     COMPREPLY=($(compgen -W "bash elvish fish powershell zsh" -- "${cur}"))
                     return 0
@@ -56,6 +120,62 @@ _tex-fmt() {
             COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
             return 0
             ;;
+        tex__fmt__help)
+            opts="lsp help"
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
+                return 0
+            fi
+            case "${prev}" in
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
+            return 0
+            ;;
+        tex__fmt__help__help)
+            opts=""
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
+                return 0
+            fi
+            case "${prev}" in
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
+            return 0
+            ;;
+        tex__fmt__help__lsp)
+            opts=""
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 3 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
+                return 0
+            fi
+            case "${prev}" in
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
+            return 0
+            ;;
+        tex__fmt__lsp)
+            opts="-h --help"
+            if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
+                COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
+                return 0
+            fi
+            case "${prev}" in
+                *)
+                    COMPREPLY=()
+                    ;;
+            esac
+            COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
+            return 0
+            ;;
     esac
 }
 
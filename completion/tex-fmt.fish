@@ -1,17 +1,67 @@
-complete -c tex-fmt -s l -l wraplen -d 'Line length for wrapping [default: 80]' -r
-complete -c tex-fmt -s t -l tabsize -d 'Number of characters to use as tab size [default: 2]' -r
-complete -c tex-fmt -l config -d 'Path to configuration file' -r -F
-complete -c tex-fmt -l completion -d 'Generate shell completion script' -r -f -a "{bash\t'',elvish\t'',fish\t'',powershell\t'',zsh\t''}"
-complete -c tex-fmt -s c -l check -d 'Check formatting, do not modify files'
-complete -c tex-fmt -s p -l print -d 'Print to stdout, do not modify files'
-complete -c tex-fmt -s n -l nowrap -d 'Do not wrap long lines'
-complete -c tex-fmt -l usetabs -d 'Use tabs instead of spaces for indentation'
-complete -c tex-fmt -s s -l stdin -d 'Process stdin as a single file, output to stdout'
-complete -c tex-fmt -l noconfig -d 'Do not read any config file'
-complete -c tex-fmt -s v -l verbose -d 'Show info messages'
-complete -c tex-fmt -s q -l quiet -d 'Hide warning messages'
-complete -c tex-fmt -l trace -d 'Show trace messages'
-complete -c tex-fmt -l man -d 'Generate man page'
-complete -c tex-fmt -l args -d 'Print arguments passed to tex-fmt and exit'
-complete -c tex-fmt -s h -l help -d 'Print help'
-complete -c tex-fmt -s V -l version -d 'Print version'
+# Print an optspec for argparse to handle cmd's options that are independent of any subcommand.
+function __fish_tex_fmt_global_optspecs
+	string join \n c/check check-idempotent diff= p/print verify n/nowrap l/wraplen= wrap-mode= join-sentences brace-wrap align-tables bib-sort bib-field-order= t/tabsize= usetabs line-ending= verbatims= lists= no-indent-envs= lines= changed since= backup= s/stdin j/jobs= config= noconfig v/verbose q/quiet trace output-format= completion= man args h/help V/version
+end
+
+function __fish_tex_fmt_needs_command
+	# Figure out if the current invocation already has a command.
+	set -l cmd (commandline -opc)
+	set -e cmd[1]
+	argparse -s (__fish_tex_fmt_global_optspecs) -- $cmd 2>/dev/null
+	or return
+	if set -q argv[1]
+		# Also print the command, so this can be used to figure out what it is.
+		echo $argv[1]
+		return 1
+	end
+	return 0
+end
+
+function __fish_tex_fmt_using_subcommand
+	set -l cmd (__fish_tex_fmt_needs_command)
+	test -z "$cmd"
+	and return 1
+	contains -- $cmd[1] $argv
+end
+
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l diff -d 'Print a diff of formatting changes, do not modify files' -r -f -a "{color\t'',plain\t''}"
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -s l -l wraplen -d 'Line length for wrapping [default: 80]' -r
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l wrap-mode -d 'Where to break lines when wrapping [default: width]' -r -f -a "{width\t'',sentence\t'',reflow\t''}"
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l bib-field-order -d 'Order of fields in bibliography entries, separated by commas' -r
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -s t -l tabsize -d 'Number of characters to use as tab size [default: 2]' -r
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l line-ending -d 'Line ending for formatted files [default: auto]' -r -f -a "{auto\t'',lf\t'',crlf\t'',native\t''}"
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l verbatims -d 'Extra verbatim environments, separated by commas' -r
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l lists -d 'Extra list environments, separated by commas' -r
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l no-indent-envs -d 'Extra environments which are not indented, separated by commas' -r
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l lines -d 'Only format lines in this range, can be repeated' -r
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l since -d 'Only format lines changed since a git revision' -r
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l backup -d 'Keep a backup of each modified file [default: .bak]' -r
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -s j -l jobs -d 'Number of files to format in parallel [default: number of CPUs]' -r
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l config -d 'Path to configuration file' -r -F
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l output-format -d 'Format used to display log messages [default: human]' -r -f -a "{human\t'',json\t'',json-lines\t'',github\t'',sarif\t''}"
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l completion -d 'Generate shell completion script' -r -f -a "{bash\t'',elvish\t'',fish\t'',powershell\t'',zsh\t''}"
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -s c -l check -d 'Check formatting, do not modify files'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l check-idempotent -d 'Check that formatting twice makes no further changes'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -s p -l print -d 'Print to stdout, do not modify files'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l verify -d 'Check that formatting only changes whitespace'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -s n -l nowrap -d 'Do not wrap long lines'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l join-sentences -d 'Join lines split in the middle of a sentence'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l brace-wrap -d 'Avoid wrapping inside braces and inline math'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l align-tables -d 'Align & columns in tables and aligned environments'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l bib-sort -d 'Sort bibliography entries by key'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l usetabs -d 'Use tabs instead of spaces for indentation'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l changed -d 'Only format lines changed since the last git commit'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -s s -l stdin -d 'Process stdin as a single file, output to stdout'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l noconfig -d 'Do not read any config file'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -s v -l verbose -d 'Show info messages'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -s q -l quiet -d 'Hide warning messages'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l trace -d 'Show trace messages'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l man -d 'Generate man page'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -l args -d 'Print arguments passed to tex-fmt and exit'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -s h -l help -d 'Print help'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -s V -l version -d 'Print version'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -a "lsp" -d 'Run as a language server over stdin and stdout'
+complete -c tex-fmt -n "__fish_tex_fmt_needs_command" -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c tex-fmt -n "__fish_tex_fmt_using_subcommand lsp" -s h -l help -d 'Print help'
+complete -c tex-fmt -n "__fish_tex_fmt_using_subcommand help; and not __fish_seen_subcommand_from lsp help" -f -a "lsp" -d 'Run as a language server over stdin and stdout'
+complete -c tex-fmt -n "__fish_tex_fmt_using_subcommand help; and not __fish_seen_subcommand_from lsp help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
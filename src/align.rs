@@ -0,0 +1,344 @@
+//! Utilities for aligning the columns of tables
+
+use crate::comments::*;
+use crate::regexes::{ENV_BEGIN, ENV_END};
+use crate::wrap::*;
+use std::iter::zip;
+use unicode_width::UnicodeWidthStr;
+
+/// Environments whose rows are separated into columns by `&`
+const TABLE_ENVS: [&str; 23] = [
+    "Bmatrix",
+    "Vmatrix",
+    "align",
+    "alignat",
+    "aligned",
+    "alignedat",
+    "array",
+    "bmatrix",
+    "cases",
+    "eqnarray",
+    "flalign",
+    "longtable",
+    "matrix",
+    "pmatrix",
+    "smallmatrix",
+    "split",
+    "tabular",
+    "tabularx",
+    "tabulary",
+    "tblr",
+    "vmatrix",
+    "xltabular",
+    "xtabular",
+];
+
+/// Separator placed after each aligned cell
+const CELL_SEPARATOR: &str = " &";
+
+/// Tracks the table environments open while formatting
+pub struct Tables {
+    /// First body line of each open table, and whether it contains another
+    open: Vec<(usize, bool)>,
+    /// First and last body lines of each table to be aligned
+    bodies: Vec<(usize, usize)>,
+    /// Lines which are ignored or verbatim, in increasing order
+    skipped: Vec<usize>,
+}
+
+impl Tables {
+    /// Construct a new empty tracker
+    pub const fn new() -> Self {
+        Self {
+            open: vec![],
+            bodies: vec![],
+            skipped: vec![],
+        }
+    }
+
+    /// Record a line which is ignored or verbatim, and must not be aligned
+    pub fn skip(&mut self, linum_new: usize) {
+        self.skipped.push(linum_new);
+    }
+
+    /// Check if a line was recorded as ignored or verbatim
+    fn is_skipped(&self, linum_new: usize) -> bool {
+        self.skipped.binary_search(&linum_new).is_ok()
+    }
+
+    /// Check if a line is a row of a currently open table
+    pub fn is_row(&self, line: &str) -> bool {
+        !self.open.is_empty() && parse_row(line).is_some()
+    }
+
+    /// Update the open tables with a line of the formatted text
+    pub fn update(&mut self, line: &str, linum_new: usize) {
+        let line = remove_comment(line, find_comment_index(line));
+        if !line.contains('\\') {
+            return;
+        }
+        let mut events: Vec<(usize, bool)> = line
+            .match_indices(ENV_BEGIN)
+            .map(|(i, _)| (i, true))
+            .chain(line.match_indices(ENV_END).map(|(i, _)| (i, false)))
+            .filter(|&(i, begin)| {
+                let len = if begin {
+                    ENV_BEGIN.len()
+                } else {
+                    ENV_END.len()
+                };
+                is_table_env(&line[i + len..])
+            })
+            .collect();
+        events.sort_unstable();
+        for (_, begin) in events {
+            if begin {
+                self.open.push((linum_new + 1, false));
+            } else if let Some((start, nested)) = self.open.pop() {
+                // Only the innermost of nested tables is aligned
+                if let Some(parent) = self.open.last_mut() {
+                    parent.1 = true;
+                }
+                if !nested && start < linum_new {
+                    self.bodies.push((start, linum_new - 1));
+                }
+            }
+        }
+    }
+}
+
+/// Check if text begins with the name of a table environment
+fn is_table_env(text: &str) -> bool {
+    text.split_once('}')
+        .is_some_and(|(env, _)| TABLE_ENVS.contains(&env.trim_end_matches('*')))
+}
+
+/// A row of a table, split into cells
+struct Row<'a> {
+    /// Leading whitespace of the line
+    indent: &'a str,
+    /// Contents of each cell, keeping a single leading space if there was
+    /// any space after the preceding `&`
+    cells: Vec<String>,
+    /// Row ending such as `\\` or `\\ \hline`
+    ending: Option<&'a str>,
+    /// Comment at the end of the line
+    comment: Option<&'a str>,
+}
+
+/// Split a line into cells at unescaped `&` outside of braces and inline
+/// verbatim, returning `None` if the line has no such `&`
+fn parse_row(line: &str) -> Option<Row<'_>> {
+    let comment_index = find_comment_index(line);
+    let comment = comment_index.map(|c| &line[c..]);
+    let text = remove_comment(line, comment_index);
+    let content = text.trim_start();
+    let indent = &text[..text.len() - content.len()];
+
+    // Keep the spacing after each `&`, so that `&=` stays together
+    let get_cell = |cell: &str| {
+        if cell.starts_with(char::is_whitespace) && !cell.trim().is_empty() {
+            format!(" {}", cell.trim())
+        } else {
+            cell.trim().to_string()
+        }
+    };
+    let mut cells = vec![];
+    let mut ending = None;
+    let mut cell_start = 0;
+    let mut depth: i32 = 0;
+    let mut verbatim_end = 0;
+    let mut prev_char: Option<char> = None;
+    for (i, c) in content.char_indices() {
+        // Inline verbatim such as `\verb|&|` never separates cells
+        if i < verbatim_end {
+            prev_char = None;
+            continue;
+        }
+        if prev_char == Some('\\') {
+            // The second backslash of `\\` starts the row ending
+            if c == '\\' && depth == 0 {
+                ending = Some(content[i - 1..].trim_end());
+                cells.push(get_cell(&content[cell_start..i - 1]));
+                break;
+            }
+            prev_char = None;
+            continue;
+        }
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '\\' => {
+                if let Some(end) = find_inline_verbatim_end(&content[i..]) {
+                    verbatim_end = i + end;
+                }
+            }
+            '&' if depth == 0 => {
+                cells.push(get_cell(&content[cell_start..i]));
+                cell_start = i + 1;
+            }
+            _ => {}
+        }
+        prev_char = Some(c);
+    }
+    if cells.is_empty() {
+        return None;
+    }
+    if ending.is_none() {
+        cells.push(get_cell(&content[cell_start..]));
+    }
+    // A line with one cell has no `&` to align
+    if cells.len() < 2 {
+        return None;
+    }
+    Some(Row {
+        indent,
+        cells,
+        ending,
+        comment,
+    })
+}
+
+/// Get the number of columns spanned by a cell
+fn get_span(cell: &str) -> usize {
+    cell.trim_start()
+        .strip_prefix("\\multicolumn{")
+        .and_then(|rest| rest.split_once('}'))
+        .and_then(|(n, _)| n.trim().parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(1)
+}
+
+/// Get the width available to a cell spanning several columns
+fn get_span_width(widths: &[usize], col: usize, span: usize) -> usize {
+    widths[col..col + span].iter().sum::<usize>()
+        + CELL_SEPARATOR.len() * (span - 1)
+}
+
+/// Compute the width of each column from the rows of a table
+///
+/// Widths are measured in terminal columns, so wide characters such as CJK
+/// ideographs count as two.
+fn get_column_widths(rows: &[Row]) -> Vec<usize> {
+    let mut widths: Vec<usize> = vec![];
+    let mut spanning = vec![];
+    for row in rows {
+        let mut col = 0;
+        for cell in &row.cells {
+            let span = get_span(cell);
+            if widths.len() < col + span {
+                widths.resize(col + span, 0);
+            }
+            let width = cell.width();
+            if span == 1 {
+                widths[col] = widths[col].max(width);
+            } else {
+                spanning.push((col, span, width));
+            }
+            col += span;
+        }
+    }
+    // Widen the last column spanned by any cell which does not fit
+    for (col, span, width) in spanning {
+        let available = get_span_width(&widths, col, span);
+        if width > available {
+            widths[col + span - 1] += width - available;
+        }
+    }
+    widths
+}
+
+/// Format a row with its cells padded to the column widths
+fn format_row(row: &Row, widths: &[usize]) -> String {
+    let mut line = row.indent.to_string();
+    let mut col = 0;
+    for (k, cell) in row.cells.iter().enumerate() {
+        let span = get_span(cell);
+        line.push_str(cell);
+        if k + 1 < row.cells.len() || row.ending.is_some() {
+            let width = get_span_width(widths, col, span);
+            let padding = width.saturating_sub(cell.width());
+            line.push_str(&" ".repeat(padding));
+        }
+        if k + 1 < row.cells.len() {
+            // Rows starting with `&` need no space before it
+            if line.len() == row.indent.len() {
+                line.push('&');
+            } else {
+                line.push_str(CELL_SEPARATOR);
+            }
+        }
+        col += span;
+    }
+    if let Some(ending) = row.ending {
+        // Pad any missing columns so that row endings line up
+        for width in &widths[col..] {
+            line.push_str(&" ".repeat(CELL_SEPARATOR.len() + width));
+        }
+        line.push(' ');
+        line.push_str(ending);
+    }
+    if let Some(comment) = row.comment {
+        line.push(' ');
+        line.push_str(comment);
+    }
+    line.trim_end().to_string()
+}
+
+/// Parse the rows in the body of a table with their line indices,
+/// returning `None` if any row is split over several lines
+///
+/// Lines for which `skipped` is true are left out of the rows.
+fn parse_rows(
+    body: &[String],
+    skipped: impl Fn(usize) -> bool,
+) -> Option<(Vec<usize>, Vec<Row<'_>>)> {
+    let mut indices = vec![];
+    let mut rows = vec![];
+    // Whether the next line of text should start a new row
+    let mut row_start = true;
+    for (i, line) in body.iter().enumerate() {
+        let text = remove_comment(line, find_comment_index(line)).trim();
+        if text.is_empty() || skipped(i) {
+            continue;
+        }
+        if !row_start {
+            return None;
+        }
+        if let Some(row) = parse_row(line) {
+            row_start = row.ending.is_some();
+            indices.push(i);
+            rows.push(row);
+        } else {
+            // Lines such as `\hline` do not continue a row
+            row_start = text.contains("\\\\") || text.starts_with('\\');
+        }
+    }
+    Some((indices, rows))
+}
+
+/// Align the `&` columns and row endings in the body of each table
+///
+/// Tables with rows split over several lines are left as they are, as are
+/// ignored and verbatim lines.
+pub fn align_tables(text: &str, tables: &Tables, line_end: &str) -> String {
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    for &(start, end) in &tables.bodies {
+        let skipped = |i| tables.is_skipped(start + i);
+        let Some((indices, rows)) = parse_rows(&lines[start - 1..end], skipped)
+        else {
+            continue;
+        };
+        let widths = get_column_widths(&rows);
+        let aligned: Vec<String> =
+            rows.iter().map(|row| format_row(row, &widths)).collect();
+        for (i, line) in zip(indices, aligned) {
+            lines[start - 1 + i] = line;
+        }
+    }
+    let mut new_text = lines.join(line_end);
+    if text.ends_with('\n') {
+        new_text.push_str(line_end);
+    }
+    new_text
+}
@@ -3,65 +3,175 @@
 use crate::cli::*;
 use crate::config::*;
 use crate::logging::*;
-use crate::Log;
+use crate::ranges::*;
+use crate::regexes::*;
+use crate::LINE_END;
 use colored::Colorize;
 use log::Level;
 use log::LevelFilter;
 use merge::Merge;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::fmt;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::thread::available_parallelism;
 
 /// Arguments passed to tex-fmt
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Args {
     /// Check formatting, do not modify files
     pub check: bool,
+    /// Check that formatting a second time makes no changes
+    pub check_idempotent: bool,
+    /// Print a diff of formatting changes, do not modify files
+    pub diff: DiffMode,
     /// Print to stdout, do not modify files
     pub print: bool,
+    /// Check that formatting only changes whitespace
+    pub verify: bool,
     /// Wrap long lines
     pub wrap: bool,
     /// Maximum allowed line length
     pub wraplen: u8,
     /// Wrap lines longer than this
     pub wrapmin: u8,
+    /// Where to break lines when wrapping
+    pub wrap_mode: WrapMode,
+    /// Join lines which were split in the middle of a sentence
+    pub join_sentences: bool,
+    /// Prefer wrapping outside of braces, brackets and inline math
+    pub brace_wrap: bool,
+    /// Align the columns of tables and aligned environments
+    pub align_tables: bool,
+    /// Sort bibliography entries by key
+    pub bib_sort: bool,
+    /// Order of fields in bibliography entries
+    pub bib_field_order: Vec<String>,
     /// Number of characters to use as tab size
     pub tabsize: u8,
     /// Characters to use for indentation
     pub tabchar: TabChar,
+    /// Line ending used in formatted files
+    pub line_ending: LineEnding,
     /// Read from stdin and output to stdout
     pub stdin: bool,
+    /// Number of files to format in parallel
+    pub jobs: usize,
+    /// Run as a language server
+    pub lsp: bool,
     /// Path to config file
     pub config: Option<PathBuf>,
     /// Verbosity level for log messages
     pub verbosity: LevelFilter,
+    /// Format used to display log messages
+    pub output_format: OutputFormat,
     /// Print arguments and exit
     pub arguments: bool,
     /// List of files to be formatted
     pub files: Vec<String>,
+    /// Ranges of lines to format, or all lines if empty
+    pub lines: Vec<LineRange>,
+    /// Only format lines changed in git
+    pub changed: bool,
+    /// Git revision to find changed lines since
+    pub since: Option<String>,
+    /// Suffix of backups of the original files, if any are kept
+    pub backup: Option<String>,
+    /// Globs selecting files to format in directories
+    pub include: Vec<String>,
+    /// Globs selecting files to skip in directories
+    pub exclude: Vec<String>,
+    /// Names of verbatim environments
+    pub verbatims: Vec<String>,
+    /// Patterns matching the beginning of verbatim environments
+    pub verbatims_begin: Vec<String>,
+    /// Patterns matching the end of verbatim environments
+    pub verbatims_end: Vec<String>,
+    /// Names of list environments
+    pub lists: Vec<String>,
+    /// Patterns matching the beginning of list environments
+    pub lists_begin: Vec<String>,
+    /// Patterns matching the end of list environments
+    pub lists_end: Vec<String>,
+    /// Names of environments which do not indent their contents
+    pub no_indent_envs: Vec<String>,
+    /// Patterns matching the beginning of no-indent environments
+    pub no_indent_envs_begin: Vec<String>,
+    /// Patterns matching the end of no-indent environments
+    pub no_indent_envs_end: Vec<String>,
+    /// Names of commands which begin on a new line
+    pub splitting: Vec<String>,
+    /// Regexes matching commands which begin on a new line
+    pub splitting_regexes: SplittingRegexes,
+    /// Arguments passed on the command line
+    pub cli_options: OptionArgs,
 }
 
+/// Arguments for the files using each config file, keyed by its path
+pub type ConfigArgs = HashMap<PathBuf, Args>;
+
 /// Arguments using Options to track CLI/config file/default values
 #[derive(Clone, Debug, Merge)]
+#[allow(missing_docs)]
 #[allow(clippy::missing_docs_in_private_items)]
 pub struct OptionArgs {
     pub check: Option<bool>,
+    pub check_idempotent: Option<bool>,
+    pub diff: Option<DiffMode>,
     pub print: Option<bool>,
+    pub verify: Option<bool>,
     pub wrap: Option<bool>,
     pub wraplen: Option<u8>,
     pub wrapmin: Option<u8>,
+    pub wrap_mode: Option<WrapMode>,
+    pub join_sentences: Option<bool>,
+    pub brace_wrap: Option<bool>,
+    pub align_tables: Option<bool>,
+    pub bib_sort: Option<bool>,
+    #[merge(strategy = merge::vec::overwrite_empty)]
+    pub bib_field_order: Vec<String>,
     pub tabsize: Option<u8>,
     pub tabchar: Option<TabChar>,
+    pub line_ending: Option<LineEnding>,
     pub stdin: Option<bool>,
+    pub jobs: Option<usize>,
+    pub lsp: Option<bool>,
     pub config: Option<PathBuf>,
     pub noconfig: Option<bool>,
     pub verbosity: Option<LevelFilter>,
+    pub output_format: Option<OutputFormat>,
     pub arguments: Option<bool>,
     #[merge(strategy = merge::vec::append)]
     pub files: Vec<String>,
+    #[merge(strategy = merge::vec::append)]
+    pub lines: Vec<LineRange>,
+    pub changed: Option<bool>,
+    pub since: Option<String>,
+    pub backup: Option<String>,
+    #[merge(strategy = merge::vec::append)]
+    pub include: Vec<String>,
+    #[merge(strategy = merge::vec::append)]
+    pub exclude: Vec<String>,
+    #[merge(strategy = merge::vec::append)]
+    pub verbatims: Vec<String>,
+    pub verbatims_replace: Option<bool>,
+    #[merge(strategy = merge::vec::append)]
+    pub lists: Vec<String>,
+    pub lists_replace: Option<bool>,
+    #[merge(strategy = merge::vec::append)]
+    pub no_indent_envs: Vec<String>,
+    pub no_indent_envs_replace: Option<bool>,
+    #[merge(strategy = merge::vec::append)]
+    pub splitting: Vec<String>,
+    #[merge(strategy = merge::vec::append)]
+    pub no_splitting: Vec<String>,
 }
 
 /// Character to use for indentation
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(missing_docs)]
 #[allow(clippy::missing_docs_in_private_items)]
 pub enum TabChar {
     Tab,
@@ -77,72 +187,431 @@ impl fmt::Display for TabChar {
     }
 }
 
+/// Where to break lines when wrapping
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(missing_docs)]
+#[allow(clippy::missing_docs_in_private_items)]
+pub enum WrapMode {
+    Width,
+    Sentence,
+    Reflow,
+}
+
+impl fmt::Display for WrapMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Width => write!(f, "width"),
+            Self::Sentence => write!(f, "sentence"),
+            Self::Reflow => write!(f, "reflow"),
+        }
+    }
+}
+
+impl WrapMode {
+    /// Parse a wrap mode from its name
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "width" => Some(Self::Width),
+            "sentence" => Some(Self::Sentence),
+            "reflow" => Some(Self::Reflow),
+            _ => None,
+        }
+    }
+}
+
+/// Line ending used in formatted files
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(missing_docs)]
+#[allow(clippy::missing_docs_in_private_items)]
+pub enum LineEnding {
+    Auto,
+    Lf,
+    Crlf,
+    Native,
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Lf => write!(f, "lf"),
+            Self::Crlf => write!(f, "crlf"),
+            Self::Native => write!(f, "native"),
+        }
+    }
+}
+
+impl LineEnding {
+    /// Parse a line ending from its name
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(Self::Auto),
+            "lf" => Some(Self::Lf),
+            "crlf" => Some(Self::Crlf),
+            "native" => Some(Self::Native),
+            _ => None,
+        }
+    }
+
+    /// Get the line ending to use for a file
+    ///
+    /// With `auto`, the ending of the first line of the file is used,
+    /// falling back to the native line ending if there is only one line.
+    #[must_use]
+    pub fn get_line_end(self, text: &str) -> &'static str {
+        match self {
+            Self::Auto => match text.find('\n') {
+                Some(i) if text[..i].ends_with('\r') => "\r\n",
+                Some(_) => "\n",
+                None => LINE_END,
+            },
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+            Self::Native => LINE_END,
+        }
+    }
+}
+
+/// Format used to display logs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[allow(missing_docs)]
+#[allow(clippy::missing_docs_in_private_items)]
+pub enum OutputFormat {
+    Human,
+    Json,
+    JsonLines,
+    Github,
+    Sarif,
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Human => write!(f, "human"),
+            Self::Json => write!(f, "json"),
+            Self::JsonLines => write!(f, "json-lines"),
+            Self::Github => write!(f, "github"),
+            Self::Sarif => write!(f, "sarif"),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Parse an output format from its name
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "human" => Some(Self::Human),
+            "json" => Some(Self::Json),
+            "json-lines" => Some(Self::JsonLines),
+            "github" => Some(Self::Github),
+            "sarif" => Some(Self::Sarif),
+            _ => None,
+        }
+    }
+}
+
+/// Style of diff to print for formatting changes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(missing_docs)]
+#[allow(clippy::missing_docs_in_private_items)]
+pub enum DiffMode {
+    Off,
+    Color,
+    Plain,
+}
+
+impl fmt::Display for DiffMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Color => write!(f, "color"),
+            Self::Plain => write!(f, "plain"),
+        }
+    }
+}
+
 impl Default for OptionArgs {
     fn default() -> Self {
         Self {
             check: Some(false),
+            check_idempotent: Some(false),
+            diff: Some(DiffMode::Off),
             print: Some(false),
+            verify: Some(false),
             wrap: Some(true),
             wraplen: Some(80),
             wrapmin: Some(70),
+            wrap_mode: Some(WrapMode::Width),
+            join_sentences: Some(false),
+            brace_wrap: Some(false),
+            align_tables: Some(false),
+            bib_sort: Some(false),
+            bib_field_order: vec![],
             tabsize: Some(2),
             tabchar: Some(TabChar::Space),
+            line_ending: Some(LineEnding::Auto),
             stdin: Some(false),
+            jobs: Some(available_parallelism().map_or(1, NonZeroUsize::get)),
+            lsp: Some(false),
             config: None,
             noconfig: Some(false),
             verbosity: Some(LevelFilter::Warn),
+            output_format: Some(OutputFormat::Human),
             arguments: Some(false),
             files: vec![],
+            lines: vec![],
+            changed: Some(false),
+            since: None,
+            backup: None,
+            include: vec![],
+            exclude: vec![],
+            verbatims: vec![],
+            verbatims_replace: Some(false),
+            lists: vec![],
+            lists_replace: Some(false),
+            no_indent_envs: vec![],
+            no_indent_envs_replace: Some(false),
+            splitting: vec![],
+            no_splitting: vec![],
+        }
+    }
+}
+
+impl OptionArgs {
+    /// Construct optional arguments with no values set
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            check: None,
+            check_idempotent: None,
+            diff: None,
+            print: None,
+            verify: None,
+            wrap: None,
+            wraplen: None,
+            wrapmin: None,
+            wrap_mode: None,
+            join_sentences: None,
+            brace_wrap: None,
+            align_tables: None,
+            bib_sort: None,
+            bib_field_order: vec![],
+            tabsize: None,
+            tabchar: None,
+            line_ending: None,
+            stdin: None,
+            jobs: None,
+            lsp: None,
+            config: None,
+            noconfig: None,
+            verbosity: None,
+            output_format: None,
+            arguments: None,
+            files: vec![],
+            lines: vec![],
+            changed: None,
+            since: None,
+            backup: None,
+            include: vec![],
+            exclude: vec![],
+            verbatims: vec![],
+            verbatims_replace: None,
+            lists: vec![],
+            lists_replace: None,
+            no_indent_envs: vec![],
+            no_indent_envs_replace: None,
+            splitting: vec![],
+            no_splitting: vec![],
         }
     }
 }
 
 /// Get all arguments from CLI, config file, and defaults, and merge them
-pub fn get_args() -> Args {
-    let mut args = get_cli_args();
-    let config_args = get_config_args(&args);
+pub fn get_args(logs: &mut Vec<Log>) -> Args {
+    let cli_args = get_cli_args();
+    let mut args = cli_args.clone();
+    let config_args = get_config_args(&args, logs);
     if let Some(c) = config_args {
         args.merge(c);
     }
     args.merge(OptionArgs::default());
-    Args::from(args)
+    Args {
+        cli_options: cli_args,
+        ..Args::from(args)
+    }
+}
+
+/// Minimum line length for wrapping, determined by the maximum
+#[must_use]
+pub const fn get_wrapmin(wraplen: u8) -> u8 {
+    if wraplen >= 50 {
+        wraplen - 10
+    } else {
+        wraplen
+    }
+}
+
+/// Combine default environment names with those provided by the user
+fn get_env_names(
+    defaults: &[&str],
+    extra: Vec<String>,
+    replace: bool,
+) -> Vec<String> {
+    let mut names: Vec<String> = if replace {
+        vec![]
+    } else {
+        defaults.iter().map(ToString::to_string).collect()
+    };
+    for name in extra {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Combine default splitting commands with those added or removed by the user
+fn get_splitting_names(extra: Vec<String>, removed: &[String]) -> Vec<String> {
+    let removed: Vec<&str> =
+        removed.iter().map(|n| n.trim_start_matches('\\')).collect();
+    let mut names: Vec<String> = vec![];
+    let defaults = SPLITTING.iter().map(|(n, _)| (*n).to_string());
+    for name in defaults.chain(extra) {
+        let name = name.trim_start_matches('\\').to_string();
+        if !names.contains(&name) && !removed.contains(&name.as_str()) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Construct patterns matching the beginning of environments
+fn get_env_begins(names: &[String]) -> Vec<String> {
+    names.iter().map(|l| format!("{ENV_BEGIN}{l}}}")).collect()
+}
+
+/// Construct patterns matching the end of environments
+fn get_env_ends(names: &[String]) -> Vec<String> {
+    names.iter().map(|l| format!("{ENV_END}{l}}}")).collect()
 }
 
 impl Args {
     /// Construct concrete arguments from optional arguments
-    fn from(args: OptionArgs) -> Self {
+    pub(crate) fn from(args: OptionArgs) -> Self {
+        let verbatims = get_env_names(
+            &VERBATIMS,
+            args.verbatims,
+            args.verbatims_replace.unwrap(),
+        );
+        let lists =
+            get_env_names(&LISTS, args.lists, args.lists_replace.unwrap());
+        let no_indent_envs = get_env_names(
+            &NO_INDENT_ENVS,
+            args.no_indent_envs,
+            args.no_indent_envs_replace.unwrap(),
+        );
+        let splitting = get_splitting_names(args.splitting, &args.no_splitting);
         Self {
             check: args.check.unwrap(),
+            check_idempotent: args.check_idempotent.unwrap(),
+            diff: args.diff.unwrap(),
             print: args.print.unwrap(),
+            verify: args.verify.unwrap(),
             wrap: args.wrap.unwrap(),
             wraplen: args.wraplen.unwrap(),
             wrapmin: args.wrapmin.unwrap(),
+            wrap_mode: args.wrap_mode.unwrap(),
+            join_sentences: args.join_sentences.unwrap(),
+            brace_wrap: args.brace_wrap.unwrap(),
+            align_tables: args.align_tables.unwrap(),
+            bib_sort: args.bib_sort.unwrap(),
+            bib_field_order: args.bib_field_order,
             tabsize: args.tabsize.unwrap(),
             tabchar: args.tabchar.unwrap(),
+            line_ending: args.line_ending.unwrap(),
             stdin: args.stdin.unwrap(),
+            jobs: args.jobs.unwrap(),
+            lsp: args.lsp.unwrap(),
             config: args.config,
             verbosity: args.verbosity.unwrap(),
+            output_format: args.output_format.unwrap(),
             arguments: args.arguments.unwrap(),
             files: args.files,
+            lines: args.lines,
+            changed: args.changed.unwrap(),
+            since: args.since,
+            backup: args.backup,
+            include: args.include,
+            exclude: args.exclude,
+            verbatims_begin: get_env_begins(&verbatims),
+            verbatims_end: get_env_ends(&verbatims),
+            verbatims,
+            lists_begin: get_env_begins(&lists),
+            lists_end: get_env_ends(&lists),
+            lists,
+            no_indent_envs_begin: get_env_begins(&no_indent_envs),
+            no_indent_envs_end: get_env_ends(&no_indent_envs),
+            no_indent_envs,
+            splitting_regexes: SplittingRegexes::new(&splitting),
+            cli_options: OptionArgs::new(),
+            splitting,
         }
     }
 
     /// Resolve conflicting arguments
     pub fn resolve(&mut self, logs: &mut Vec<Log>) -> u8 {
-        let mut exit_code = 0;
+        // Stop if the config file could not be read
+        let mut exit_code =
+            u8::from(logs.iter().any(|log| log.level == Level::Error));
 
         // stdin implies print
         self.print |= self.stdin;
 
         // Set wrapmin
-        self.wrapmin = if self.wraplen >= 50 {
-            self.wraplen - 10
-        } else {
-            self.wraplen
-        };
-
-        // Check files are passed if no --stdin
-        if !self.stdin && self.files.is_empty() {
+        self.wrapmin = get_wrapmin(self.wraplen);
+
+        // Use at least one job
+        self.jobs = self.jobs.max(1);
+
+        // A revision implies formatting changed lines
+        self.changed |= self.since.is_some();
+
+        // Format changed files in the current directory by default
+        if self.changed && !self.stdin && self.files.is_empty() {
+            self.files.push(".".to_string());
+        }
+
+        // Check changed lines are not combined with other line selections
+        if self.changed && (self.stdin || !self.lines.is_empty()) {
+            record_file_log(
+                logs,
+                Level::Error,
+                "",
+                "Do not use --changed or --since with --stdin or --lines.",
+            );
+            exit_code = 1;
+        }
+
+        // A second pass would not format the same lines as the first
+        if self.check_idempotent && (self.changed || !self.lines.is_empty()) {
+            record_file_log(
+                logs,
+                Level::Error,
+                "",
+                "Do not use --check-idempotent with --changed, --since or --lines.",
+            );
+            exit_code = 1;
+        }
+
+        // Check files are passed if no --stdin or lsp
+        if !self.stdin && !self.lsp && self.files.is_empty() {
             record_file_log(
                 logs,
                 Level::Error,
@@ -175,6 +644,89 @@ impl Args {
     }
 }
 
+impl Args {
+    /// Combine the command line arguments with the config file of some files
+    ///
+    /// Only options affecting how files are formatted are taken from the
+    /// config file, and the others are kept from these arguments.
+    fn with_config(&self, config_args: OptionArgs) -> Self {
+        let mut args = self.cli_options.clone();
+        args.merge(config_args);
+        args.merge(OptionArgs::default());
+        let args = Self::from(args);
+        Self {
+            check: self.check,
+            check_idempotent: self.check_idempotent,
+            diff: self.diff,
+            print: self.print,
+            verify: self.verify,
+            wrapmin: get_wrapmin(args.wraplen),
+            stdin: self.stdin,
+            jobs: self.jobs,
+            lsp: self.lsp,
+            verbosity: self.verbosity,
+            output_format: self.output_format,
+            arguments: self.arguments,
+            files: vec![],
+            lines: self.lines.clone(),
+            changed: self.changed,
+            since: self.since.clone(),
+            backup: self.backup.clone(),
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            cli_options: self.cli_options.clone(),
+            ..args
+        }
+    }
+
+    /// Read the nearest config file of each file, if it differs from the
+    /// config file already used, returning `None` if any cannot be read
+    pub fn get_file_config_args(
+        &self,
+        files: &[String],
+        logs: &mut Vec<Log>,
+    ) -> Option<ConfigArgs> {
+        let mut configs = ConfigArgs::new();
+        // A config file given on the command line is used for every file
+        if self.cli_options.config.is_some()
+            || self.cli_options.noconfig == Some(true)
+        {
+            return Some(configs);
+        }
+        let global = self.config.as_ref().and_then(|c| c.canonicalize().ok());
+        let mut failed = vec![];
+        for config in files.iter().filter_map(|f| find_file_config(f)) {
+            if Some(&config) == global.as_ref()
+                || configs.contains_key(&config)
+                || failed.contains(&config)
+            {
+                continue;
+            }
+            if let Some(config_args) = read_config(&config, logs) {
+                configs.insert(config, self.with_config(config_args));
+            } else {
+                failed.push(config);
+            }
+        }
+        failed.is_empty().then_some(configs)
+    }
+
+    /// Get the arguments for a file from the nearest config file
+    #[must_use]
+    pub fn for_file<'a>(
+        &'a self,
+        file: &str,
+        configs: &'a ConfigArgs,
+    ) -> &'a Self {
+        if configs.is_empty() {
+            return self;
+        }
+        find_file_config(file)
+            .and_then(|config| configs.get(&config))
+            .unwrap_or(self)
+    }
+}
+
 impl Default for Args {
     fn default() -> Self {
         Self::from(OptionArgs::default())
@@ -193,17 +745,58 @@ fn display_arg_line(
     Ok(())
 }
 
+/// Print a list field from `Args`, one value per line
+fn display_arg_list(
+    f: &mut fmt::Formatter,
+    name: &str,
+    values: &[String],
+) -> fmt::Result {
+    if !values.is_empty() {
+        display_arg_line(f, name, &values[0])?;
+        for value in &values[1..] {
+            write!(
+                f,
+                "\n  {:<width$} {}",
+                "".bold().to_string(),
+                value,
+                width = 20
+            )?;
+        }
+    }
+    Ok(())
+}
+
 impl fmt::Display for Args {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", "tex-fmt".magenta().bold())?;
         display_arg_line(f, "check", &self.check.to_string())?;
+        display_arg_line(
+            f,
+            "check-idempotent",
+            &self.check_idempotent.to_string(),
+        )?;
+        display_arg_line(f, "diff", &self.diff.to_string())?;
         display_arg_line(f, "print", &self.print.to_string())?;
+        display_arg_line(f, "verify", &self.verify.to_string())?;
         display_arg_line(f, "wrap", &self.wrap.to_string())?;
         display_arg_line(f, "wraplen", &self.wraplen.to_string())?;
         display_arg_line(f, "wrapmin", &self.wrapmin.to_string())?;
+        display_arg_line(f, "wrap-mode", &self.wrap_mode.to_string())?;
+        display_arg_line(
+            f,
+            "join-sentences",
+            &self.join_sentences.to_string(),
+        )?;
+        display_arg_line(f, "brace-wrap", &self.brace_wrap.to_string())?;
+        display_arg_line(f, "align-tables", &self.align_tables.to_string())?;
+        display_arg_line(f, "bib-sort", &self.bib_sort.to_string())?;
+        display_arg_list(f, "bib-field-order", &self.bib_field_order)?;
         display_arg_line(f, "tabsize", &self.tabsize.to_string())?;
         display_arg_line(f, "tabchar", &self.tabchar.to_string())?;
+        display_arg_line(f, "line-ending", &self.line_ending.to_string())?;
         display_arg_line(f, "stdin", &self.stdin.to_string())?;
+        display_arg_line(f, "jobs", &self.jobs.to_string())?;
+        display_arg_line(f, "lsp", &self.lsp.to_string())?;
         match &self.config {
             None => display_arg_line(f, "config", "None")?,
             Some(c) => display_arg_line(f, "config", &c.display().to_string())?,
@@ -213,21 +806,32 @@ impl fmt::Display for Args {
             "verbosity",
             &self.verbosity.to_string().to_lowercase(),
         )?;
+        display_arg_line(f, "output-format", &self.output_format.to_string())?;
+        display_arg_list(f, "verbatims", &self.verbatims)?;
+        display_arg_list(f, "lists", &self.lists)?;
+        display_arg_list(f, "no-indent-envs", &self.no_indent_envs)?;
+        display_arg_list(f, "splitting", &self.splitting)?;
+        display_arg_list(f, "include", &self.include)?;
+        display_arg_list(f, "exclude", &self.exclude)?;
+        display_arg_list(f, "files", &self.files)?;
+        display_arg_line(f, "changed", &self.changed.to_string())?;
+        display_arg_line(f, "since", self.since.as_deref().unwrap_or("None"))?;
+        display_arg_line(
+            f,
+            "backup",
+            self.backup.as_deref().unwrap_or("None"),
+        )?;
+        display_arg_list(
+            f,
+            "lines",
+            &self
+                .lines
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+        )?;
 
-        if !self.files.is_empty() {
-            display_arg_line(f, "files", &self.files[0])?;
-            for file in &self.files[1..] {
-                write!(
-                    f,
-                    "\n  {:<width$} {}",
-                    "".bold().to_string(),
-                    file,
-                    width = 20
-                )?;
-            }
-        }
-
-        // Do not print `arguments` or `noconfig` fields
+        // Do not print `arguments`, `noconfig`, or precomputed pattern fields
         Ok(())
     }
 }
@@ -0,0 +1,577 @@
+//! Utilities for formatting bibliography files
+
+use crate::args::*;
+use crate::logging::*;
+use log::Level::Warn;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Entry types whose contents are kept as they are
+const VERBATIM_ENTRIES: [&str; 3] = ["comment", "preamble", "string"];
+
+/// Fields whose values are kept as they are, without wrapping
+const VERBATIM_FIELDS: [&str; 6] =
+    ["doi", "eprint", "file", "pdf", "url", "urlraw"];
+
+/// Field of a bibliography entry
+struct Field<'a> {
+    /// Name of the field, in lower case
+    name: String,
+    /// Value of the field, with normalised spacing and delimiters
+    value: String,
+    /// Comment at the end of the line of the field
+    comment: Option<&'a str>,
+}
+
+/// Bibliography entry such as `@article{key, ...}`
+struct Entry<'a> {
+    /// Type of the entry, in lower case
+    kind: String,
+    /// Citation key
+    key: &'a str,
+    /// Fields of the entry
+    fields: Vec<Field<'a>>,
+}
+
+/// Contents of a piece of a bibliography file
+enum Content<'a> {
+    /// Text outside of any entry, such as comments
+    Text,
+    /// Text which is kept as it is, such as `@string` definitions
+    Verbatim,
+    /// Line beginning an entry which could not be parsed
+    Unparsed,
+    /// Parsed bibliography entry
+    Entry(Entry<'a>),
+}
+
+/// Piece of a bibliography file
+struct Item<'a> {
+    /// Parsed contents
+    content: Content<'a>,
+    /// Original text
+    raw: &'a str,
+    /// Line number of the start of the item in the original file
+    linum_old: usize,
+}
+
+impl Item<'_> {
+    /// Check if this is a comment directly above the next item
+    fn is_adjacent(&self) -> bool {
+        let trailing = &self.raw[self.raw.trim_end().len()..];
+        match self.content {
+            Content::Text => {
+                !self.raw.trim().is_empty()
+                    && trailing.matches('\n').count() <= 1
+            }
+            Content::Unparsed => true,
+            _ => false,
+        }
+    }
+
+    /// Line number of the end of the item in the original file
+    fn linum_end(&self) -> usize {
+        self.linum_old + self.raw.trim_end().matches('\n').count()
+    }
+}
+
+/// Check if a file is a bibliography file
+pub fn is_bib_file(file: &str) -> bool {
+    Path::new(file)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("bib"))
+}
+
+/// Find the end of a group starting with an opening delimiter, counting
+/// nested braces
+fn find_group_end(text: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        if c == open || (c == '{' && open != '{') {
+            depth += 1;
+        } else if c == close || (c == '}' && close != '}') {
+            depth -= 1;
+        }
+        if depth == 0 {
+            return Some(i + c.len_utf8());
+        }
+    }
+    None
+}
+
+/// Find the end of a quoted string starting with `"`
+fn find_quote_end(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '"' if depth == 0 => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Check if every brace in some text is closed
+fn has_balanced_braces(text: &str) -> bool {
+    let mut depth: i32 = 0;
+    for c in text.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => {}
+        }
+        if depth < 0 {
+            return false;
+        }
+    }
+    depth == 0
+}
+
+/// Normalise the spacing of a field value and use braces in place of quotes
+fn normalise_value(name: &str, value: &str, pieces: usize) -> String {
+    if VERBATIM_FIELDS.contains(&name) {
+        return value.to_string();
+    }
+    let value = value.split_whitespace().collect::<Vec<&str>>().join(" ");
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) if pieces == 1 && has_balanced_braces(inner) => {
+            format!("{{{inner}}}")
+        }
+        _ => value,
+    }
+}
+
+/// Parse the value of a field, returning its length and number of pieces
+/// joined by `#`
+fn parse_value(text: &str, close: char) -> Option<(usize, usize)> {
+    let mut i = 0;
+    let mut pieces = 0;
+    loop {
+        let rest = &text[i..];
+        let c = rest.chars().next()?;
+        if c == ',' || c == close {
+            return Some((i, pieces));
+        } else if c.is_whitespace() || c == '#' {
+            i += c.len_utf8();
+            continue;
+        }
+        let len = match c {
+            '{' => find_group_end(rest, '{', '}')?,
+            '"' => find_quote_end(rest)?,
+            _ => rest
+                .find(|c: char| {
+                    c.is_whitespace() || c == ',' || c == '#' || c == close
+                })
+                .unwrap_or(rest.len()),
+        };
+        pieces += 1;
+        i += len;
+    }
+}
+
+/// Parse an entry starting with `@`, returning its contents and length
+fn parse_entry(text: &str) -> Option<(Content<'_>, usize)> {
+    let kind_len = text[1..]
+        .find(|c: char| !c.is_alphanumeric())
+        .filter(|&n| n > 0)?;
+    let kind = text[1..=kind_len].to_lowercase();
+    let mut i = 1 + kind_len;
+    i += text[i..].len() - text[i..].trim_start().len();
+    let open = text[i..].chars().next()?;
+    let close = match open {
+        '{' => '}',
+        '(' => ')',
+        _ => return None,
+    };
+    if VERBATIM_ENTRIES.contains(&kind.as_str()) {
+        let len = find_group_end(&text[i..], open, close)?;
+        return Some((Content::Verbatim, i + len));
+    }
+    i += 1;
+
+    // The key ends at the first comma, or the end of an entry with no fields
+    let key_len = text[i..].find([',', close, '\n'])?;
+    let key = text[i..i + key_len].trim();
+    i += key_len;
+
+    let mut fields = vec![];
+    // Entries with comments between their fields are kept as they are
+    let mut verbatim = false;
+    loop {
+        let rest = &text[i..];
+        let trimmed =
+            rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        i += rest.len() - trimmed.len();
+        if trimmed.starts_with(close) {
+            i += 1;
+            break;
+        } else if trimmed.starts_with('%') {
+            verbatim = true;
+            i += trimmed.find('\n')?;
+            continue;
+        }
+        let name_len = trimmed
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .filter(|&n| n > 0)?;
+        let name = &trimmed[..name_len];
+        if name.contains(['{', '}', '(', ')', '"', '#', '%', ',']) {
+            return None;
+        }
+        i += name_len;
+        let rest = text[i..].trim_start().strip_prefix('=')?;
+        i = text.len() - rest.len();
+        let (value_len, pieces) = parse_value(&text[i..], close)?;
+        let name = name.to_lowercase();
+        let value =
+            normalise_value(&name, text[i..i + value_len].trim(), pieces);
+        i += value_len;
+
+        // Keep any comment after the value on the same line
+        let rest = &text[i..];
+        let trimmed = rest
+            .trim_start_matches([' ', '\t'])
+            .strip_prefix(',')
+            .unwrap_or(rest)
+            .trim_start_matches([' ', '\t']);
+        let comment = if trimmed.starts_with('%') {
+            let len = trimmed.find('\n')?;
+            i = text.len() - trimmed.len() + len;
+            Some(trimmed[..len].trim_end())
+        } else {
+            None
+        };
+        fields.push(Field {
+            name,
+            value,
+            comment,
+        });
+    }
+    if verbatim {
+        return Some((Content::Verbatim, i));
+    }
+    Some((Content::Entry(Entry { kind, key, fields }), i))
+}
+
+/// Check if a line begins an ignore block
+fn is_ignore_begin(line: &str) -> bool {
+    line.trim_end().ends_with("% tex-fmt: off")
+}
+
+/// Check if a line ends an ignore block
+fn is_ignore_end(line: &str) -> bool {
+    line.trim_end().ends_with("% tex-fmt: on")
+}
+
+/// Split a bibliography file into entries and the text between them
+fn parse_bib(text: &str) -> Vec<Item<'_>> {
+    let mut items = vec![];
+    let mut text_start = 0;
+    let mut pos = 0;
+    let mut linum = 1;
+    let mut counted = 0;
+    // Count lines up to a position, starting from the last one counted
+    let mut linum_at = |pos: usize| {
+        if pos < counted {
+            linum -= text[pos..counted].matches('\n').count();
+        } else {
+            linum += text[counted..pos].matches('\n').count();
+        }
+        counted = pos;
+        linum
+    };
+
+    while pos < text.len() {
+        let line_len =
+            text[pos..].find('\n').map_or(text.len() - pos, |n| n + 1);
+        let line = &text[pos..pos + line_len];
+        let start = pos + line.len() - line.trim_start().len();
+
+        let parsed = if line.trim_start().starts_with('@') {
+            // Lines which do not begin a valid entry are kept as they are
+            Some(parse_entry(&text[start..]).map_or(
+                (Content::Unparsed, start, pos + line_len),
+                |(content, len)| (content, start, start + len),
+            ))
+        } else if is_ignore_begin(line) {
+            // Keep everything up to the end of the ignore block
+            let mut end = pos + line_len;
+            while end < text.len() {
+                let next_len =
+                    text[end..].find('\n').map_or(text.len() - end, |n| n + 1);
+                end += next_len;
+                if is_ignore_end(&text[end - next_len..end]) {
+                    break;
+                }
+            }
+            Some((Content::Verbatim, pos, end))
+        } else {
+            None
+        };
+
+        let Some((content, start, end)) = parsed else {
+            pos += line_len;
+            continue;
+        };
+        if text_start < start {
+            items.push(Item {
+                content: Content::Text,
+                raw: &text[text_start..start],
+                linum_old: linum_at(text_start),
+            });
+        }
+        items.push(Item {
+            content,
+            raw: &text[start..end],
+            linum_old: linum_at(start),
+        });
+        text_start = end;
+        pos = end;
+    }
+    if text_start < text.len() {
+        items.push(Item {
+            content: Content::Text,
+            raw: &text[text_start..],
+            linum_old: linum_at(text_start),
+        });
+    }
+    items
+}
+
+/// Sort runs of entries by key, keeping any comment directly above an
+/// entry with it
+fn sort_entries(items: Vec<Item<'_>>) -> Vec<Item<'_>> {
+    /// Get the sort key of a comment and entry
+    fn get_key(unit: &[Item]) -> String {
+        match &unit[unit.len() - 1].content {
+            Content::Entry(entry) => entry.key.to_lowercase(),
+            _ => String::new(),
+        }
+    }
+
+    /// Sort a run of entries and add it to the sorted items
+    fn flush<'b>(run: &mut Vec<Vec<Item<'b>>>, sorted: &mut Vec<Item<'b>>) {
+        run.sort_by_cached_key(|unit| get_key(unit));
+        sorted.extend(run.drain(..).flatten());
+    }
+
+    let mut sorted = vec![];
+    let mut run = vec![];
+    let mut comment: Option<Item> = None;
+    for item in items {
+        match item.content {
+            Content::Entry(_) => {
+                let mut unit = vec![];
+                if let Some(c) = comment.take() {
+                    if c.is_adjacent() {
+                        unit.push(c);
+                    } else {
+                        flush(&mut run, &mut sorted);
+                        sorted.push(c);
+                    }
+                }
+                unit.push(item);
+                run.push(unit);
+            }
+            Content::Text if item.raw.trim().is_empty() => {}
+            Content::Text => {
+                if let Some(c) = comment.take() {
+                    flush(&mut run, &mut sorted);
+                    sorted.push(c);
+                }
+                comment = Some(item);
+            }
+            Content::Verbatim | Content::Unparsed => {
+                flush(&mut run, &mut sorted);
+                sorted.extend(comment.take());
+                sorted.push(item);
+            }
+        }
+    }
+    flush(&mut run, &mut sorted);
+    sorted.extend(comment);
+    sorted
+}
+
+/// Wrap a long field line at spaces in its value
+fn wrap_field(line: String, value_start: usize, args: &Args) -> Vec<String> {
+    let indent = get_bib_indent(args).repeat(2);
+    let mut lines = vec![];
+    let mut line = line;
+    while line.chars().count() > args.wraplen.into() {
+        let mut wrap_point = None;
+        let mut prev_char = None;
+        for (width, (i, c)) in (1..).zip(line.char_indices()) {
+            if width > args.wrapmin.into() && wrap_point.is_some() {
+                break;
+            }
+            if c == ' ' && i > value_start && prev_char != Some('\\') {
+                wrap_point = Some(i);
+            }
+            prev_char = Some(c);
+        }
+        let Some(p) = wrap_point else {
+            break;
+        };
+        let next_line = format!("{indent}{}", &line[p + 1..]);
+        line.truncate(p);
+        lines.push(line);
+        line = next_line;
+    }
+    lines.push(line);
+    lines
+}
+
+/// Get the string used to indent the fields of an entry
+fn get_bib_indent(args: &Args) -> String {
+    match args.tabchar {
+        TabChar::Tab => "\t".to_string(),
+        TabChar::Space => " ".repeat(args.tabsize.into()),
+    }
+}
+
+/// Format an entry with one field per line and aligned `=` signs
+fn format_entry(entry: &Entry, args: &Args) -> Vec<String> {
+    if entry.fields.is_empty() {
+        return vec![format!("@{}{{{}}}", entry.kind, entry.key)];
+    }
+    let indent = get_bib_indent(args);
+    let mut fields: Vec<&Field> = entry.fields.iter().collect();
+    if !args.bib_field_order.is_empty() {
+        fields.sort_by_key(|field| {
+            args.bib_field_order
+                .iter()
+                .position(|f| f.eq_ignore_ascii_case(&field.name))
+                .unwrap_or(args.bib_field_order.len())
+        });
+    }
+    let width = fields
+        .iter()
+        .map(|f| f.name.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut lines = vec![format!("@{}{{{},", entry.kind, entry.key)];
+    for field in fields {
+        let mut line =
+            format!("{indent}{:<width$} = {},", field.name, field.value);
+        // Lines with comments are not wrapped
+        if let Some(comment) = field.comment {
+            line.push(' ');
+            line.push_str(comment);
+            lines.push(line);
+        } else if args.wrap && !VERBATIM_FIELDS.contains(&field.name.as_str()) {
+            let value_start = indent.len() + width + 3;
+            lines.extend(wrap_field(line, value_start, args));
+        } else {
+            lines.push(line);
+        }
+    }
+    lines.push("}".to_string());
+    lines
+}
+
+/// Format the text outside of entries, dropping surrounding blank lines
+/// and repeated blank lines
+fn format_text(raw: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in raw.lines().map(str::trim_end) {
+        if !line.is_empty() || lines.last().is_some_and(|l| !l.is_empty()) {
+            lines.push(line.to_string());
+        }
+    }
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    lines
+}
+
+/// Format a bibliography file, with each field of an entry on its own line
+pub fn format_bib(
+    old_text: &str,
+    file: &str,
+    args: &Args,
+    logs: &mut Vec<Log>,
+) -> String {
+    let restricted = !args.lines.is_empty();
+    let line_end = args.line_ending.get_line_end(old_text);
+    let mut items = parse_bib(old_text);
+    if args.bib_sort && !restricted {
+        items = sort_entries(items);
+    }
+
+    let mut new_lines: Vec<String> = vec![];
+    let mut new_text = String::with_capacity(old_text.len());
+    let mut keys = HashSet::new();
+    let mut adjacent = false;
+    for item in &items {
+        // Entries are separated from the previous item by a blank line
+        let linum_new = new_lines.len()
+            + usize::from(!new_lines.is_empty() && !adjacent)
+            + 1;
+        let lines = match &item.content {
+            Content::Entry(entry) => {
+                if !keys.insert(entry.key.to_lowercase()) {
+                    record_line_log(
+                        logs,
+                        Warn,
+                        file,
+                        linum_new,
+                        item.linum_old,
+                        item.raw.lines().next().unwrap_or_default(),
+                        "Duplicate bibliography key.",
+                    );
+                }
+                format_entry(entry, args)
+            }
+            Content::Unparsed => {
+                record_line_log(
+                    logs,
+                    Warn,
+                    file,
+                    linum_new,
+                    item.linum_old,
+                    item.raw.trim_end(),
+                    "Could not parse bibliography entry.",
+                );
+                vec![item.raw.trim_end().to_string()]
+            }
+            Content::Text => format_text(item.raw),
+            Content::Verbatim => {
+                item.raw.lines().map(|l| l.trim_end().to_string()).collect()
+            }
+        };
+
+        // Only entries in the selected lines are formatted
+        if restricted {
+            let selected = matches!(item.content, Content::Entry(_))
+                && args.lines.iter().any(|r| {
+                    r.start <= item.linum_end() && item.linum_old <= r.end
+                });
+            if selected {
+                new_text.push_str(&lines.join(line_end));
+            } else {
+                new_text.push_str(item.raw);
+            }
+            continue;
+        }
+
+        if lines.is_empty() {
+            continue;
+        }
+        if !new_lines.is_empty() && !adjacent {
+            new_lines.push(String::new());
+        }
+        adjacent = item.is_adjacent();
+        new_lines.extend(lines);
+    }
+
+    if !restricted {
+        for line in new_lines {
+            new_text.push_str(&line);
+            new_text.push_str(line_end);
+        }
+    }
+    new_text
+}
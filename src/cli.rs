@@ -1,6 +1,7 @@
 //! Functionality to parse CLI arguments
 
 use crate::args::*;
+use crate::ranges::*;
 use clap::ArgMatches;
 use clap_complete::{generate, Shell};
 use clap_mangen::Man;
@@ -20,6 +21,7 @@ fn get_flag(arg_matches: &ArgMatches, flag: &str) -> Option<bool> {
 }
 
 /// Parse CLI arguments into `OptionArgs` struct
+#[allow(clippy::too_many_lines)]
 pub fn get_cli_args() -> OptionArgs {
     let mut command = get_cli_command();
     let arg_matches = command.clone().get_matches();
@@ -42,6 +44,11 @@ pub fn get_cli_args() -> OptionArgs {
     } else {
         None
     };
+    let diff = match arg_matches.get_one::<String>("diff").map(String::as_str) {
+        Some("plain") => Some(DiffMode::Plain),
+        Some(_) => Some(DiffMode::Color),
+        None => None,
+    };
     let tabchar = if arg_matches.get_flag("usetabs") {
         Some(TabChar::Tab)
     } else {
@@ -58,22 +65,75 @@ pub fn get_cli_args() -> OptionArgs {
     };
     let args = OptionArgs {
         check: get_flag(&arg_matches, "check"),
+        check_idempotent: get_flag(&arg_matches, "check-idempotent"),
+        diff,
         print: get_flag(&arg_matches, "print"),
+        verify: get_flag(&arg_matches, "verify"),
         wrap,
         wraplen: arg_matches.get_one::<u8>("wraplen").copied(),
         wrapmin: None,
+        wrap_mode: arg_matches
+            .get_one::<String>("wrap-mode")
+            .and_then(|x| WrapMode::from_name(x)),
+        join_sentences: get_flag(&arg_matches, "join-sentences"),
+        brace_wrap: get_flag(&arg_matches, "brace-wrap"),
+        align_tables: get_flag(&arg_matches, "align-tables"),
+        bib_sort: get_flag(&arg_matches, "bib-sort"),
+        bib_field_order: arg_matches
+            .get_many::<String>("bib-field-order")
+            .unwrap_or_default()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<String>>(),
         tabsize: arg_matches.get_one::<u8>("tabsize").copied(),
         tabchar,
+        line_ending: arg_matches
+            .get_one::<String>("line-ending")
+            .and_then(|x| LineEnding::from_name(x)),
         stdin: get_flag(&arg_matches, "stdin"),
+        jobs: arg_matches.get_one::<usize>("jobs").copied(),
+        lsp: arg_matches.subcommand_matches("lsp").map(|_| true),
         config: arg_matches.get_one::<PathBuf>("config").cloned(),
         noconfig: get_flag(&arg_matches, "noconfig"),
         verbosity,
+        output_format: arg_matches
+            .get_one::<String>("output-format")
+            .and_then(|x| OutputFormat::from_name(x)),
         arguments: get_flag(&arg_matches, "args"),
         files: arg_matches
             .get_many::<String>("files")
             .unwrap_or_default()
             .map(ToOwned::to_owned)
             .collect::<Vec<String>>(),
+        lines: arg_matches
+            .get_many::<(usize, usize)>("lines")
+            .unwrap_or_default()
+            .map(|(start, end)| LineRange::new(*start, *end))
+            .collect::<Vec<LineRange>>(),
+        changed: get_flag(&arg_matches, "changed"),
+        since: arg_matches.get_one::<String>("since").cloned(),
+        backup: arg_matches.get_one::<String>("backup").cloned(),
+        include: vec![],
+        exclude: vec![],
+        verbatims: arg_matches
+            .get_many::<String>("verbatims")
+            .unwrap_or_default()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<String>>(),
+        verbatims_replace: None,
+        lists: arg_matches
+            .get_many::<String>("lists")
+            .unwrap_or_default()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<String>>(),
+        lists_replace: None,
+        no_indent_envs: arg_matches
+            .get_many::<String>("no-indent-envs")
+            .unwrap_or_default()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<String>>(),
+        no_indent_envs_replace: None,
+        splitting: vec![],
+        no_splitting: vec![],
     };
     args
 }
@@ -17,6 +17,22 @@ fn get_cli_command() -> Command {
                 .action(SetTrue)
                 .help("Check formatting, do not modify files"),
         )
+        .arg(
+            Arg::new("check-idempotent")
+                .long("check-idempotent")
+                .action(SetTrue)
+                .help("Check that formatting twice makes no further changes"),
+        )
+        .arg(
+            Arg::new("diff")
+                .long("diff")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("color")
+                .value_parser(["color", "plain"])
+                .value_name("style")
+                .help("Print a diff of formatting changes, do not modify files"),
+        )
         .arg(
             Arg::new("print")
                 .short('p')
@@ -24,6 +40,12 @@ fn get_cli_command() -> Command {
                 .action(SetTrue)
                 .help("Print to stdout, do not modify files"),
         )
+        .arg(
+            Arg::new("verify")
+                .long("verify")
+                .action(SetTrue)
+                .help("Check that formatting only changes whitespace"),
+        )
         .arg(
             Arg::new("nowrap")
                 .short('n')
@@ -38,6 +60,45 @@ fn get_cli_command() -> Command {
                 .value_parser(value_parser!(u8))
                 .help("Line length for wrapping [default: 80]"),
         )
+        .arg(
+            Arg::new("wrap-mode")
+                .long("wrap-mode")
+                .value_parser(["width", "sentence", "reflow"])
+                .value_name("mode")
+                .help("Where to break lines when wrapping [default: width]"),
+        )
+        .arg(
+            Arg::new("join-sentences")
+                .long("join-sentences")
+                .action(SetTrue)
+                .help("Join lines split in the middle of a sentence"),
+        )
+        .arg(
+            Arg::new("brace-wrap")
+                .long("brace-wrap")
+                .action(SetTrue)
+                .help("Avoid wrapping inside braces and inline math"),
+        )
+        .arg(
+            Arg::new("align-tables")
+                .long("align-tables")
+                .action(SetTrue)
+                .help("Align & columns in tables and aligned environments"),
+        )
+        .arg(
+            Arg::new("bib-sort")
+                .long("bib-sort")
+                .action(SetTrue)
+                .help("Sort bibliography entries by key"),
+        )
+        .arg(
+            Arg::new("bib-field-order")
+                .long("bib-field-order")
+                .value_delimiter(',')
+                .action(Append)
+                .value_name("fields")
+                .help("Order of fields in bibliography entries, separated by commas"),
+        )
         .arg(
             Arg::new("tabsize")
                 .short('t')
@@ -51,6 +112,66 @@ fn get_cli_command() -> Command {
                 .action(SetTrue)
                 .help("Use tabs instead of spaces for indentation"),
         )
+        .arg(
+            Arg::new("line-ending")
+                .long("line-ending")
+                .value_parser(["auto", "lf", "crlf", "native"])
+                .value_name("ending")
+                .help("Line ending for formatted files [default: auto]"),
+        )
+        .arg(
+            Arg::new("verbatims")
+                .long("verbatims")
+                .value_delimiter(',')
+                .action(Append)
+                .value_name("envs")
+                .help("Extra verbatim environments, separated by commas"),
+        )
+        .arg(
+            Arg::new("lists")
+                .long("lists")
+                .value_delimiter(',')
+                .action(Append)
+                .value_name("envs")
+                .help("Extra list environments, separated by commas"),
+        )
+        .arg(
+            Arg::new("no-indent-envs")
+                .long("no-indent-envs")
+                .value_delimiter(',')
+                .action(Append)
+                .value_name("envs")
+                .help("Extra environments which are not indented, separated by commas"),
+        )
+        .arg(
+            Arg::new("lines")
+                .long("lines")
+                .action(Append)
+                .value_parser(parse_line_range)
+                .value_name("start:end")
+                .help("Only format lines in this range, can be repeated"),
+        )
+        .arg(
+            Arg::new("changed")
+                .long("changed")
+                .action(SetTrue)
+                .help("Only format lines changed since the last git commit"),
+        )
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .value_name("rev")
+                .help("Only format lines changed since a git revision"),
+        )
+        .arg(
+            Arg::new("backup")
+                .long("backup")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value(".bak")
+                .value_name("suffix")
+                .help("Keep a backup of each modified file [default: .bak]"),
+        )
         .arg(
             Arg::new("stdin")
                 .short('s')
@@ -58,6 +179,13 @@ fn get_cli_command() -> Command {
                 .action(SetTrue)
                 .help("Process stdin as a single file, output to stdout"),
         )
+        .arg(
+            Arg::new("jobs")
+                .short('j')
+                .long("jobs")
+                .value_parser(value_parser!(usize))
+                .help("Number of files to format in parallel [default: number of CPUs]"),
+        )
         .arg(
             Arg::new("config")
                 .long("config")
@@ -90,6 +218,13 @@ fn get_cli_command() -> Command {
                 .action(SetTrue)
                 .help("Show trace messages"),
         )
+        .arg(
+            Arg::new("output-format")
+                .long("output-format")
+                .value_parser(["human", "json", "json-lines", "github", "sarif"])
+                .value_name("format")
+                .help("Format used to display log messages [default: human]"),
+        )
         .arg(
             Arg::new("completion")
                 .long("completion")
@@ -114,4 +249,20 @@ fn get_cli_command() -> Command {
                 .action(Append)
                 .help("List of files to be formatted"),
         )
+        .subcommand(
+            Command::new("lsp")
+                .about("Run as a language server over stdin and stdout"),
+        )
+}
+
+/// Parse a range of lines given as `START:END`
+fn parse_line_range(s: &str) -> Result<(usize, usize), String> {
+    let err = || "expected START:END with 1 <= START <= END".to_string();
+    let (start, end) = s.split_once(':').ok_or_else(err)?;
+    let start: usize = start.trim().parse().map_err(|_| err())?;
+    let end: usize = end.trim().parse().map_err(|_| err())?;
+    if start == 0 || start > end {
+        return Err(err());
+    }
+    Ok((start, end))
 }
@@ -1,12 +1,15 @@
 //! Read arguments from a config file
 
 use crate::args::*;
+use crate::logging::*;
 use dirs::config_dir;
+use log::Level::{Error, Warn};
 use log::LevelFilter;
+use merge::Merge;
+use serde::Deserialize;
 use std::env::current_dir;
 use std::fs::{metadata, read_to_string};
-use std::path::PathBuf;
-use toml::Table;
+use std::path::{Path, PathBuf};
 
 /// Config file name
 const CONFIG: &str = "tex-fmt.toml";
@@ -16,24 +19,24 @@ fn resolve_config_path(args: &OptionArgs) -> Option<PathBuf> {
     // Do not read config file
     if args.noconfig == Some(true) {
         return None;
-    };
+    }
     // Named path passed as cli arg
     if args.config.is_some() {
         return args.config.clone();
-    };
+    }
     // Config file in current directory
     if let Ok(mut config) = current_dir() {
         config.push(CONFIG);
         if config.exists() {
             return Some(config);
-        };
+        }
     }
     // Config file at git repository root
     if let Some(mut config) = find_git_root() {
         config.push(CONFIG);
         if config.exists() {
             return Some(config);
-        };
+        }
     }
     // Config file in user home config directory
     if let Some(mut config) = config_dir() {
@@ -41,21 +44,41 @@ fn resolve_config_path(args: &OptionArgs) -> Option<PathBuf> {
         config.push(CONFIG);
         if config.exists() {
             return Some(config);
-        };
+        }
+    }
+    None
+}
+
+/// Find the nearest config file in the directory of a file or its parents,
+/// up to the root of its git repository
+///
+/// The file itself need not exist, as for unsaved documents in an editor.
+pub fn find_file_config(file: &str) -> Option<PathBuf> {
+    let dir = Path::new(file).parent()?;
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    for dir in dir.canonicalize().ok()?.ancestors() {
+        let config = dir.join(CONFIG);
+        if config.exists() {
+            return Some(config);
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
     }
     None
 }
 
 /// Get the git repository root directory
-fn find_git_root() -> Option<PathBuf> {
+pub fn find_git_root() -> Option<PathBuf> {
     let mut depth = 0;
     let mut current_dir = current_dir().unwrap();
     while depth < 100 {
         depth += 1;
-        if metadata(current_dir.join(".git"))
-            .map(|m| m.is_dir())
-            .unwrap_or(false)
-        {
+        if metadata(current_dir.join(".git")).is_ok_and(|m| m.is_dir()) {
             return Some(current_dir);
         }
         if !current_dir.pop() {
@@ -65,58 +88,244 @@ fn find_git_root() -> Option<PathBuf> {
     None
 }
 
-/// Parse arguments from a config file path
-pub fn get_config_args(args: &OptionArgs) -> Option<OptionArgs> {
-    let config_path = resolve_config_path(args);
-    #[allow(clippy::question_mark)]
-    if config_path.is_none() {
-        return None;
-    };
-    let config_string = config_path
-        .clone()
-        .unwrap()
-        .into_os_string()
-        .into_string()
-        .unwrap();
-    let config = read_to_string(config_path.clone().unwrap()).unwrap();
-    let config = config.parse::<Table>().unwrap_or_else(|_| {
-        panic!("Failed to read config file at {config_string}")
-    });
+/// Verbosity levels which can be set in a config file
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(clippy::missing_docs_in_private_items)]
+enum Verbosity {
+    Error,
+    Quiet,
+    Warn,
+    Verbose,
+    Trace,
+}
 
-    let verbosity = match config.get("verbosity").map(|x| x.as_str().unwrap()) {
-        Some("error" | "quiet") => Some(LevelFilter::Error),
-        Some("warn") => Some(LevelFilter::Warn),
-        Some("verbose") => Some(LevelFilter::Info),
-        Some("trace") => Some(LevelFilter::Trace),
-        _ => None,
-    };
+impl From<Verbosity> for LevelFilter {
+    fn from(verbosity: Verbosity) -> Self {
+        match verbosity {
+            Verbosity::Error | Verbosity::Quiet => Self::Error,
+            Verbosity::Warn => Self::Warn,
+            Verbosity::Verbose => Self::Info,
+            Verbosity::Trace => Self::Trace,
+        }
+    }
+}
 
-    let tabchar = match config.get("tabchar").map(|x| x.as_str().unwrap()) {
-        Some("tab") => Some(TabChar::Tab),
-        Some("space") => Some(TabChar::Space),
-        _ => None,
-    };
+/// Options which can be set in a config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+#[allow(clippy::missing_docs_in_private_items)]
+struct Config {
+    extends: Option<PathBuf>,
+    check: Option<bool>,
+    check_idempotent: Option<bool>,
+    diff: Option<DiffMode>,
+    print: Option<bool>,
+    verify: Option<bool>,
+    wrap: Option<bool>,
+    wraplen: Option<u8>,
+    wrapmin: Option<u8>,
+    wrap_mode: Option<WrapMode>,
+    join_sentences: Option<bool>,
+    brace_wrap: Option<bool>,
+    align_tables: Option<bool>,
+    bib_sort: Option<bool>,
+    bib_field_order: Vec<String>,
+    tabsize: Option<u8>,
+    tabchar: Option<TabChar>,
+    line_ending: Option<LineEnding>,
+    stdin: Option<bool>,
+    jobs: Option<usize>,
+    verbosity: Option<Verbosity>,
+    output_format: Option<OutputFormat>,
+    backup: Option<String>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    verbatims: Vec<String>,
+    verbatims_replace: Option<bool>,
+    lists: Vec<String>,
+    lists_replace: Option<bool>,
+    no_indent_envs: Vec<String>,
+    no_indent_envs_replace: Option<bool>,
+    splitting: Vec<String>,
+    no_splitting: Vec<String>,
+}
+
+impl Config {
+    /// Convert config file options into arguments
+    fn into_args(self, config_path: PathBuf) -> OptionArgs {
+        OptionArgs {
+            check: self.check,
+            check_idempotent: self.check_idempotent,
+            diff: self.diff,
+            print: self.print,
+            verify: self.verify,
+            wrap: self.wrap,
+            wraplen: self.wraplen,
+            wrapmin: self.wrapmin,
+            wrap_mode: self.wrap_mode,
+            join_sentences: self.join_sentences,
+            brace_wrap: self.brace_wrap,
+            align_tables: self.align_tables,
+            bib_sort: self.bib_sort,
+            bib_field_order: self.bib_field_order,
+            tabsize: self.tabsize,
+            tabchar: self.tabchar,
+            line_ending: self.line_ending,
+            stdin: self.stdin,
+            jobs: self.jobs,
+            lsp: None,
+            config: Some(config_path),
+            noconfig: None,
+            verbosity: self.verbosity.map(LevelFilter::from),
+            output_format: self.output_format,
+            arguments: None,
+            files: vec![],
+            lines: vec![],
+            changed: None,
+            since: None,
+            backup: self.backup,
+            include: self.include,
+            exclude: self.exclude,
+            verbatims: self.verbatims,
+            verbatims_replace: self.verbatims_replace,
+            lists: self.lists,
+            lists_replace: self.lists_replace,
+            no_indent_envs: self.no_indent_envs,
+            no_indent_envs_replace: self.no_indent_envs_replace,
+            splitting: self.splitting,
+            no_splitting: self.no_splitting,
+        }
+    }
+}
+
+/// Get the line and column numbers of a position in some text
+fn get_line_column(text: &str, pos: usize) -> (usize, usize) {
+    let before = &text[..pos];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, column)
+}
+
+/// Find the line number on which a top-level key is set
+fn find_key_line(text: &str, key: &str) -> Option<usize> {
+    (1..).zip(text.lines()).find_map(|(linum, line)| {
+        line.trim_start()
+            .strip_prefix(key)
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+            .then_some(linum)
+    })
+}
+
+/// Record an error from parsing a config file
+fn record_config_error(
+    logs: &mut Vec<Log>,
+    file: &str,
+    text: &str,
+    error: &toml::de::Error,
+) {
+    let message = error.message().lines().collect::<Vec<&str>>().join(", ");
+    let msg = error.span().map_or_else(
+        || format!("Invalid config file: {message}."),
+        |span| {
+            let (line, column) = get_line_column(text, span.start);
+            let key = text
+                .lines()
+                .nth(line - 1)
+                .and_then(|l| l.split_once('='))
+                .map(|(key, _)| key.trim())
+                .filter(|key| !key.is_empty());
+            key.map_or_else(
+                || {
+                    format!(
+                        "Invalid config file at line {line}, \
+                        column {column}: {message}."
+                    )
+                },
+                |key| {
+                    format!(
+                        "Invalid config for {key} at line {line}, \
+                        column {column}: {message}."
+                    )
+                },
+            )
+        },
+    );
+    record_file_log(logs, Error, file, &msg);
+}
 
-    let args = OptionArgs {
-        check: config.get("check").map(|x| x.as_bool().unwrap()),
-        print: config.get("print").map(|x| x.as_bool().unwrap()),
-        wrap: config.get("wrap").map(|x| x.as_bool().unwrap()),
-        wraplen: config
-            .get("wraplen")
-            .map(|x| x.as_integer().unwrap().try_into().unwrap()),
-        wrapmin: config
-            .get("wrapmin")
-            .map(|x| x.as_integer().unwrap().try_into().unwrap()),
-        tabsize: config
-            .get("tabsize")
-            .map(|x| x.as_integer().unwrap().try_into().unwrap()),
-        tabchar,
-        stdin: config.get("stdin").map(|x| x.as_bool().unwrap()),
-        config: config_path,
-        noconfig: None,
-        verbosity,
-        arguments: None,
-        files: vec![],
+/// Parse a single config file
+///
+/// Errors are recorded and return `None`, and unknown keys are warned about.
+fn parse_config(config_path: &Path, logs: &mut Vec<Log>) -> Option<Config> {
+    let file = config_path.to_string_lossy();
+    let text = match read_to_string(config_path) {
+        Ok(text) => text,
+        Err(e) => {
+            let msg = format!("Could not read config file: {e}.");
+            record_file_log(logs, Error, &file, &msg);
+            return None;
+        }
+    };
+    let mut unknown_keys = vec![];
+    let deserializer = toml::Deserializer::new(&text);
+    let config: Config = match serde_ignored::deserialize(deserializer, |key| {
+        unknown_keys.push(key.to_string());
+    }) {
+        Ok(config) => config,
+        Err(e) => {
+            record_config_error(logs, &file, &text, &e);
+            return None;
+        }
     };
+    for key in unknown_keys {
+        let msg = find_key_line(&text, &key).map_or_else(
+            || format!("Unknown config key {key}."),
+            |line| format!("Unknown config key {key} at line {line}."),
+        );
+        record_file_log(logs, Warn, &file, &msg);
+    }
+    Some(config)
+}
+
+/// Read arguments from a config file and any config files it extends
+///
+/// Options in a config file take priority over those in the file it extends.
+pub fn read_config(
+    config_path: &Path,
+    logs: &mut Vec<Log>,
+) -> Option<OptionArgs> {
+    let mut args = OptionArgs::new();
+    let mut path = config_path.to_path_buf();
+    let mut seen = vec![];
+    loop {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if seen.contains(&canonical) {
+            let file = path.to_string_lossy();
+            record_file_log(logs, Error, &file, "Config file extends itself.");
+            return None;
+        }
+        seen.push(canonical);
+        let config = parse_config(&path, logs)?;
+        // Paths to extended config files are relative to the config file
+        let extends = config
+            .extends
+            .as_ref()
+            .map(|e| path.parent().unwrap_or_else(|| Path::new("")).join(e));
+        args.merge(config.into_args(path));
+        let Some(extends) = extends else {
+            break;
+        };
+        path = extends;
+    }
     Some(args)
 }
+
+/// Parse arguments from a config file path
+pub fn get_config_args(
+    args: &OptionArgs,
+    logs: &mut Vec<Log>,
+) -> Option<OptionArgs> {
+    let config_path = resolve_config_path(args)?;
+    read_config(&config_path, logs)
+}
@@ -1,20 +1,27 @@
 //! Core methodology for formatting a file
 
+use crate::align::*;
 use crate::args::*;
+use crate::bib::*;
+use crate::git::*;
 use crate::ignore::*;
 use crate::indent::*;
 use crate::logging::*;
+use crate::lsp::*;
+use crate::ranges::*;
 use crate::read::*;
-use crate::regexes::{ENV_BEGIN, ENV_END, ITEM, RE_SPLITTING};
+use crate::regexes::{ENV_BEGIN, ENV_END, ITEM, RE_ITEM};
 use crate::subs::*;
 use crate::verbatim::*;
 use crate::wrap::*;
 use crate::write::*;
-use crate::LINE_END;
 use log::Level::{Info, Warn};
-use std::iter::zip;
+use std::iter::{zip, Peekable};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
 /// Central function to format a file
+#[allow(clippy::too_many_lines)]
 pub fn format_file(
     old_text: &str,
     file: &str,
@@ -23,14 +30,38 @@ pub fn format_file(
 ) -> String {
     record_file_log(logs, Info, file, "Formatting started.");
 
+    // Bibliography files have their own formatter
+    if is_bib_file(file) {
+        let new_text = format_bib(old_text, file, args, logs);
+        record_file_log(logs, Info, file, "Formatting complete.");
+        return new_text;
+    }
+
+    // When formatting selected lines, keep the others byte-identical
+    let restricted = !args.lines.is_empty();
+    let original_lines: Vec<&str> = old_text.split_inclusive('\n').collect();
+    let line_end = args.line_ending.get_line_end(old_text);
+
     // Clean the source file and zip its lines with line numbers
-    let old_text = clean_text(old_text, args);
-    let mut old_lines = zip(1.., old_text.lines());
+    let old_text = if restricted {
+        old_text.to_string()
+    } else {
+        clean_text(old_text, line_end, args)
+    };
+    let mut old_lines = zip(1.., old_text.lines()).peekable();
 
     // Initialise
     let mut state = State::new();
-    let mut queue: Vec<(usize, String)> = vec![];
+    let mut queue: Vec<(usize, String, Option<String>)> = vec![];
     let mut new_text = String::with_capacity(2 * old_text.len());
+    let mut tables = Tables::new();
+    let align = args.align_tables && !restricted;
+    let reflow = args.wrap && args.wrap_mode == WrapMode::Reflow;
+    let join_sentences = args.wrap
+        && args.wrap_mode == WrapMode::Sentence
+        && args.join_sentences;
+    let join = reflow || join_sentences;
+    let track_tables = align || join_sentences;
 
     // Select the character used for indentation.
     let indent_char = match args.tabchar {
@@ -39,9 +70,9 @@ pub fn format_file(
     };
 
     loop {
-        if let Some((linum_old, mut line)) = queue.pop() {
+        if let Some((linum_old, mut line, continued)) = queue.pop() {
             // Read the patterns present on this line.
-            let pattern = Pattern::new(&line);
+            let pattern = Pattern::new(&line, args);
 
             // Temporary state for working on this line.
             let mut temp_state = state.clone();
@@ -49,25 +80,82 @@ pub fn format_file(
             // Update the state with the line number from the queue.
             temp_state.linum_old = linum_old;
 
-            // If the line should not be ignored ...
-            if !set_ignore_and_report(
+            // Check if the line is in the selected ranges.
+            let selected = !restricted || contains_line(&args.lines, linum_old);
+
+            let ignored = set_ignore_and_report(
                 &line,
                 &mut temp_state,
                 logs,
                 file,
                 &pattern,
-            ) {
+                args,
+            );
+
+            // Clean selected lines which were not cleaned with the file.
+            if restricted && selected {
+                if line.is_empty() && new_text.ends_with(&line_end.repeat(2)) {
+                    continue;
+                }
+                if args.tabchar != TabChar::Tab {
+                    line = remove_tabs(&line, args);
+                }
+                line.truncate(line.trim_end_matches(' ').len());
+            }
+
+            if !ignored && !selected {
+                // Only track the indent of lines outside the selected ranges.
+                calculate_indent(
+                    &line,
+                    &mut temp_state,
+                    logs,
+                    file,
+                    args,
+                    &pattern,
+                );
+            } else if !ignored {
                 // Check if the line should be split because of a pattern
                 // that should begin on a new line.
-                if needs_split(&line, &pattern) {
+                if needs_split(&line, &pattern, args) {
                     // Split the line into two ...
                     let (this_line, next_line) =
                         split_line(&line, &temp_state, file, args, logs);
                     // ... and queue the second part for formatting.
-                    queue.push((linum_old, next_line.to_string()));
+                    queue.push((linum_old, next_line.to_string(), None));
                     line = this_line.to_string();
                 }
 
+                // Break the line after a sentence if requested, and loop
+                // back to format each part. Display math, table rows and
+                // parts of wrapped sentences are never broken.
+                let sentence_line = continued.is_none()
+                    && !temp_state.display_math
+                    && !(track_tables && tables.is_row(&line));
+                if let Some([this_line, next_line]) =
+                    apply_sentence_wrap(line.trim_start(), sentence_line, args)
+                {
+                    queue.push((linum_old, next_line.to_string(), None));
+                    queue.push((linum_old, this_line.to_string(), None));
+                    continue;
+                }
+
+                // Join the next line of a paragraph onto the last sentence
+                // of this line if requested, before wrapping, so that each
+                // sentence is broken and wrapped as a whole.
+                if join_sentences && continued.is_none() && queue.is_empty() {
+                    if let Some(joined) = take_join(
+                        &line,
+                        &mut old_lines,
+                        restricted,
+                        &temp_state,
+                        file,
+                        args,
+                    ) {
+                        queue.push((linum_old, joined, None));
+                        continue;
+                    }
+                }
+
                 // Calculate the indent based on the current state
                 // and the patterns in the line.
                 let indent = calculate_indent(
@@ -85,11 +173,26 @@ pub fn format_file(
                         .expect("Visual indent is non-negative.");
 
                 // Wrap the line before applying the indent, and loop back
-                // if the line needed wrapping.
-                if needs_wrap(line.trim_start(), indent_length, args) {
+                // if the line needed wrapping. Rows of aligned tables are
+                // never wrapped.
+                if needs_wrap(line.trim_start(), indent_length, args)
+                    && !(align && tables.is_row(&line))
+                {
+                    // When joining, the first part of the line is not
+                    // dedented by any brackets closed later in the line, so
+                    // wrap it at the indent of the previous line to make
+                    // sure it fits.
+                    let wrap_indent_length = if join {
+                        let actual = state.indent.actual.max(0).unsigned_abs();
+                        indent_length.max(
+                            usize::from(actual) * usize::from(args.tabsize),
+                        )
+                    } else {
+                        indent_length
+                    };
                     let wrapped_lines = apply_wrap(
                         line.trim_start(),
-                        indent_length,
+                        wrap_indent_length,
                         &temp_state,
                         file,
                         args,
@@ -98,11 +201,48 @@ pub fn format_file(
                     if let Some([this_line, next_line_start, next_line]) =
                         wrapped_lines
                     {
+                        // Parts of a sentence which would be joined again
+                        // when formatting the output are not broken or
+                        // joined on their own, so that they give the same
+                        // result as the whole sentence.
+                        let next_line = [next_line_start, next_line].concat();
+                        let prefix = continued.as_ref().map_or_else(
+                            || this_line.to_string(),
+                            |prefix| join_lines(prefix, this_line),
+                        );
+                        let next_continued = (join_sentences
+                            && needs_join(
+                                &prefix,
+                                &next_line,
+                                &temp_state,
+                                file,
+                                args,
+                            ))
+                        .then_some(prefix);
+                        queue.push((linum_old, next_line, next_continued));
                         queue.push((
                             linum_old,
-                            [next_line_start, next_line].concat(),
+                            this_line.to_string(),
+                            continued,
                         ));
-                        queue.push((linum_old, this_line.to_string()));
+                        continue;
+                    }
+                }
+
+                // When reflowing, join the next line of a paragraph onto
+                // the last part of this line, and loop back to format them
+                // together. Joins are decided on the lines as they are
+                // written, so that formatting again makes the same joins.
+                if reflow && queue.is_empty() {
+                    if let Some(joined) = take_join(
+                        &line,
+                        &mut old_lines,
+                        restricted,
+                        &temp_state,
+                        file,
+                        args,
+                    ) {
+                        queue.push((linum_old, joined, None));
                         continue;
                     }
                 }
@@ -111,13 +251,34 @@ pub fn format_file(
                 line = apply_indent(&line, &indent, args, indent_char);
             }
 
+            // Track display math for joining lines, and tables for
+            // aligning columns.
+            if !ignored {
+                if join {
+                    temp_state.display_math =
+                        get_display_math(&line, temp_state.display_math);
+                }
+                if track_tables {
+                    tables.update(&line, temp_state.linum_new);
+                }
+            } else if align {
+                tables.skip(temp_state.linum_new);
+            }
+
             // Add line to new text
             state = temp_state;
-            new_text.push_str(&line);
-            new_text.push_str(LINE_END);
+            if restricted && selected {
+                new_text.push_str(line.trim_end_matches(' '));
+                new_text.push_str(line_end);
+            } else if selected {
+                new_text.push_str(&line);
+                new_text.push_str(line_end);
+            } else {
+                new_text.push_str(original_lines[linum_old - 1]);
+            }
             state.linum_new += 1;
         } else if let Some((linum_old, line)) = old_lines.next() {
-            queue.push((linum_old, line.to_string()));
+            queue.push((linum_old, line.to_string(), None));
         } else {
             break;
         }
@@ -131,11 +292,38 @@ pub fn format_file(
         record_file_log(logs, Warn, file, &msg);
     }
 
-    new_text = remove_trailing_spaces(&new_text);
+    if align {
+        new_text = align_tables(&new_text, &tables, line_end);
+    }
+
+    if !restricted {
+        new_text = remove_trailing_spaces(&new_text, line_end);
+    }
+
     record_file_log(logs, Info, file, "Formatting complete.");
     new_text
 }
 
+/// Take the next line of the file if it should be joined onto a line, and
+/// return the joined line
+fn take_join<'a>(
+    line: &str,
+    old_lines: &mut Peekable<impl Iterator<Item = (usize, &'a str)>>,
+    restricted: bool,
+    state: &State,
+    file: &str,
+    args: &Args,
+) -> Option<String> {
+    let (linum_next, next_line) = old_lines.peek()?;
+    let next_selected = !restricted || contains_line(&args.lines, *linum_next);
+    if !next_selected || !needs_join(line, next_line, state, file, args) {
+        return None;
+    }
+    let joined = join_lines(line, next_line);
+    old_lines.next();
+    Some(joined)
+}
+
 /// Sets the `ignore` and `verbatim` flags in the given [State] based on
 /// `line` and returns whether `line` should be ignored by formatting.
 fn set_ignore_and_report(
@@ -144,24 +332,25 @@ fn set_ignore_and_report(
     logs: &mut Vec<Log>,
     file: &str,
     pattern: &Pattern,
+    args: &Args,
 ) -> bool {
     temp_state.ignore = get_ignore(line, temp_state, logs, file, true);
     temp_state.verbatim =
-        get_verbatim(line, temp_state, logs, file, true, pattern);
+        get_verbatim(line, temp_state, logs, file, true, pattern, args);
 
     temp_state.verbatim.visual || temp_state.ignore.visual
 }
 
 /// Cleans the given text by removing extra line breaks and trailing spaces,
 /// and also tabs if they shouldn't be used.
-fn clean_text(text: &str, args: &Args) -> String {
-    let mut text = remove_extra_newlines(text);
+fn clean_text(text: &str, line_end: &str, args: &Args) -> String {
+    let mut text = remove_extra_newlines(text, line_end);
 
     if args.tabchar != TabChar::Tab {
         text = remove_tabs(&text, args);
     }
 
-    text = remove_trailing_spaces(&text);
+    text = remove_trailing_spaces(&text, line_end);
 
     text
 }
@@ -181,6 +370,8 @@ pub struct State {
     pub verbatim: Verbatim,
     /// Line number in the new file of the last non-indented line
     pub linum_last_zero_indent: usize,
+    /// Whether display math is open at the end of the line
+    pub display_math: bool,
 }
 
 impl State {
@@ -193,6 +384,7 @@ impl State {
             indent: Indent::new(),
             verbatim: Verbatim::new(),
             linum_last_zero_indent: 1,
+            display_math: false,
         }
     }
 }
@@ -211,14 +403,17 @@ pub struct Pattern {
 
 impl Pattern {
     /// Check if a string contains patterns
-    pub fn new(s: &str) -> Self {
-        // If splitting does not match, no patterns are present
-        if RE_SPLITTING.is_match(s) {
+    pub fn new(s: &str, args: &Args) -> Self {
+        // If there are no commands, no patterns are present
+        if s.contains('\\') {
             Self {
                 contains_env_begin: s.contains(ENV_BEGIN),
                 contains_env_end: s.contains(ENV_END),
-                contains_item: s.contains(ITEM),
-                contains_splitting: true,
+                contains_item: s.contains(ITEM) && RE_ITEM.is_match(s),
+                contains_splitting: args
+                    .splitting_regexes
+                    .splitting
+                    .is_match(s),
             }
         } else {
             Self {
@@ -236,25 +431,133 @@ const fn indents_return_to_zero(state: &State) -> bool {
     state.indent.actual == 0
 }
 
+/// Result of reading and formatting a single file
+struct FileResult {
+    /// File name, original text and formatted text, if the file was read
+    output: Option<(String, String, String)>,
+    /// Logs recorded while reading and formatting the file
+    logs: Vec<Log>,
+}
+
+/// Read and format a single file, recording logs in a separate buffer
+///
+/// If `changed` is given, only the changed lines of the file are formatted.
+fn read_and_format(
+    file: &str,
+    args: &Args,
+    configs: &ConfigArgs,
+    changed: Option<&ChangedLines>,
+) -> FileResult {
+    let args = args.for_file(file, configs);
+    let changed_args =
+        changed
+            .and_then(|c| get_file_changes(c, file))
+            .map(|lines| Args {
+                lines: lines.clone(),
+                ..args.clone()
+            });
+    let args = changed_args.as_ref().unwrap_or(args);
+    let mut logs = Vec::<Log>::new();
+    let output = read(file, &mut logs).map(|(file, text)| {
+        let new_text = format_file(&text, &file, args, &mut logs);
+        (file, text, new_text)
+    });
+    FileResult { output, logs }
+}
+
+/// Read and format files in parallel, returning results in file order
+fn read_and_format_all(
+    files: &[String],
+    args: &Args,
+    configs: &ConfigArgs,
+    changed: Option<&ChangedLines>,
+) -> Vec<FileResult> {
+    let jobs = args.jobs.clamp(1, files.len().max(1));
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, FileResult)> = thread::scope(|s| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut results = vec![];
+                    // Take the next unformatted file until none remain
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(file) = files.get(i) else {
+                            break;
+                        };
+                        results.push((
+                            i,
+                            read_and_format(file, args, configs, changed),
+                        ));
+                    }
+                    results
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().expect("Formatting thread panicked."))
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
 /// Run tex-fmt with the provided arguments
-pub fn run(args: &Args, logs: &mut Vec<Log>) -> u8 {
+pub fn run(
+    args: &Args,
+    logs: &mut Vec<Log>,
+    statuses: &mut Vec<FileStatus>,
+) -> u8 {
     let mut exit_code = 0;
-    if args.stdin {
+    if args.lsp {
+        exit_code = run_lsp(args, logs);
+    } else if args.stdin {
         if let Some((file, text)) = read_stdin(logs) {
             let new_text = format_file(&text, &file, args, logs);
-            exit_code = process_output(args, &file, &text, &new_text, logs);
+            let (file_exit_code, status) =
+                process_file(args, &file, &text, &new_text, logs);
+            exit_code = file_exit_code;
+            statuses.push(status);
         } else {
             exit_code = 1;
+            statuses.push(FileStatus::error("<stdin>"));
         }
-    } else {
-        for file in &args.files {
-            if let Some((file, text)) = read(file, logs) {
-                let new_text = format_file(&text, &file, args, logs);
-                exit_code = process_output(args, &file, &text, &new_text, logs);
+    } else if let Some(mut files) = find_files(args, logs) {
+        // Only format files with lines changed in git
+        let changed = if args.changed {
+            let Some(changed) = get_changed_lines(args, logs) else {
+                return 1;
+            };
+            files.retain(|f| get_file_changes(&changed, f).is_some());
+            Some(changed)
+        } else {
+            None
+        };
+
+        // Read the config file nearest to each file
+        let Some(configs) = args.get_file_config_args(&files, logs) else {
+            return 1;
+        };
+
+        // Merge the logs and outputs of each file in a fixed order
+        let results =
+            read_and_format_all(&files, args, &configs, changed.as_ref());
+        for (file, result) in zip(&files, results) {
+            logs.extend(result.logs);
+            if let Some((file, text, new_text)) = result.output {
+                let args = args.for_file(&file, &configs);
+                let (file_exit_code, status) =
+                    process_file(args, &file, &text, &new_text, logs);
+                exit_code = exit_code.max(file_exit_code);
+                statuses.push(status);
             } else {
                 exit_code = 1;
+                statuses.push(FileStatus::error(file));
             }
         }
+    } else {
+        exit_code = 1;
     }
     exit_code
 }
@@ -0,0 +1,178 @@
+//! Utilities for finding lines changed in a git repository
+
+use crate::args::*;
+use crate::config::*;
+use crate::logging::*;
+use crate::ranges::*;
+use log::Level::{Error, Trace};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Revision compared against when none is given
+const DEFAULT_REV: &str = "HEAD";
+
+/// Changed lines in each file, keyed by canonical path
+pub type ChangedLines = HashMap<PathBuf, Vec<LineRange>>;
+
+/// Run a git command in `dir` and return its output
+fn run_git(
+    dir: &Path,
+    git_args: &[&str],
+    logs: &mut Vec<Log>,
+) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-c")
+        .arg("core.quotePath=false")
+        .args(git_args)
+        .current_dir(dir)
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).to_string())
+        }
+        Ok(output) => {
+            let msg = format!(
+                "git {} failed: {}",
+                git_args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            record_file_log(logs, Error, "", &msg);
+            None
+        }
+        Err(e) => {
+            let msg = format!("Could not run git: {e}");
+            record_file_log(logs, Error, "", &msg);
+            None
+        }
+    }
+}
+
+/// Parse the new line range from a hunk header such as `@@ -1,2 +3,4 @@`
+pub fn parse_hunk_header(line: &str) -> Option<LineRange> {
+    let new = line.split(' ').find(|s| s.starts_with('+'))?;
+    let mut numbers = new[1..].split(',');
+    let start: usize = numbers.next()?.parse().ok()?;
+    let count: usize = numbers.next().map_or(Some(1), |n| n.parse().ok())?;
+    // Hunks which only delete lines do not select any lines
+    if count == 0 {
+        return None;
+    }
+    Some(LineRange::new(start, start + count - 1))
+}
+
+/// Remove the quotes and escapes which git adds around unusual paths, such
+/// as `"b/a\"b.tex"`
+pub fn unquote_path(path: &str) -> String {
+    let Some(quoted) = path.strip_prefix('"').and_then(|p| p.strip_suffix('"'))
+    else {
+        return path.to_string();
+    };
+    let mut bytes = vec![];
+    let mut chars = quoted.bytes();
+    while let Some(b) = chars.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        let escaped = match chars.next() {
+            Some(b'a') => 0x07,
+            Some(b'b') => 0x08,
+            Some(b't') => b'\t',
+            Some(b'n') => b'\n',
+            Some(b'v') => 0x0b,
+            Some(b'f') => 0x0c,
+            Some(b'r') => b'\r',
+            // Other bytes are written as three octal digits
+            Some(d @ b'0'..=b'3') => chars
+                .by_ref()
+                .take(2)
+                .fold(d - b'0', |n, d| n * 8 + (d - b'0')),
+            Some(c) => c,
+            None => break,
+        };
+        bytes.push(escaped);
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+/// Parse the output of `git diff --unified=0` into changed line ranges
+fn parse_diff(diff: &str, root: &Path) -> ChangedLines {
+    let mut changed = ChangedLines::new();
+    let mut file: Option<PathBuf> = None;
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            // Deleted files have no new path
+            let path = unquote_path(path);
+            file = path
+                .strip_prefix("b/")
+                .and_then(|p| root.join(p).canonicalize().ok());
+        } else if line.starts_with("@@") {
+            if let (Some(file), Some(range)) = (&file, parse_hunk_header(line))
+            {
+                changed.entry(file.clone()).or_default().push(range);
+            }
+        }
+    }
+    changed
+}
+
+/// Find the lines changed since a git revision, including untracked files
+pub fn get_changed_lines(
+    args: &Args,
+    logs: &mut Vec<Log>,
+) -> Option<ChangedLines> {
+    let Some(root) = find_git_root() else {
+        record_file_log(logs, Error, "", "Not in a git repository.");
+        return None;
+    };
+    let rev = args.since.as_deref().unwrap_or(DEFAULT_REV);
+    // A revision starting with `-` would be read by git as an option
+    if rev.starts_with('-') {
+        let msg = format!("Invalid git revision {rev}.");
+        record_file_log(logs, Error, "", &msg);
+        return None;
+    }
+    let diff = run_git(
+        &root,
+        &[
+            "diff",
+            "--unified=0",
+            "--no-color",
+            "--no-ext-diff",
+            "--no-renames",
+            // Override the `diff.noprefix` and `diff.mnemonicPrefix` options
+            "--src-prefix=a/",
+            "--dst-prefix=b/",
+            rev,
+            "--",
+        ],
+        logs,
+    )?;
+    let mut changed = parse_diff(&diff, &root);
+
+    // Untracked files are entirely new
+    let untracked = run_git(
+        &root,
+        &["ls-files", "-z", "--others", "--exclude-standard"],
+        logs,
+    )?;
+    for path in untracked.split_terminator('\0') {
+        if let Ok(path) = root.join(path).canonicalize() {
+            changed.insert(path, vec![LineRange::new(1, usize::MAX)]);
+        }
+    }
+
+    let msg = format!("Found {} changed files since {rev}.", changed.len());
+    record_file_log(logs, Trace, "", &msg);
+    Some(changed)
+}
+
+/// Get the changed lines of a file, if it has any
+pub fn get_file_changes<'a>(
+    changed: &'a ChangedLines,
+    file: &str,
+) -> Option<&'a Vec<LineRange>> {
+    let path = Path::new(file).canonicalize().ok()?;
+    changed.get(&path)
+}
@@ -14,6 +14,100 @@ const OPENS: [char; 3] = ['{', '(', '['];
 /// Closing delimiters
 const CLOSES: [char; 3] = ['}', ')', ']'];
 
+/// Primitive commands which open an indented block
+const PRIMITIVE_OPENS: [&str; 3] = ["begingroup", "bgroup", "makeatletter"];
+/// Primitive commands which close an indented block
+const PRIMITIVE_CLOSES: [&str; 4] = ["egroup", "endgroup", "fi", "makeatother"];
+/// Commands whose following control sequences are names being defined,
+/// rather than commands being used
+const DEFINITIONS: [&str; 11] = [
+    "DeclareRobustCommand",
+    "def",
+    "edef",
+    "gdef",
+    "let",
+    "newcommand",
+    "newif",
+    "providecommand",
+    "renewcommand",
+    "show",
+    "xdef",
+];
+
+/// Primitive commands affecting indentation
+#[derive(Debug, PartialEq, Eq)]
+enum Primitive {
+    /// Opens a block, such as `\if...` or `\bgroup`
+    Open,
+    /// Closes a block, such as `\fi` or `\egroup`
+    Close,
+    /// Separates branches of a conditional, such as `\else` or `\or`
+    Branch,
+}
+
+/// Check if a command is a TeX conditional, rather than a LaTeX command
+/// such as `\ifthenelse{...}` or `\ifdefempty` from `etoolbox`
+fn is_conditional(name: &str, rest: &str) -> bool {
+    name.starts_with("if")
+        && !rest.starts_with(['{', '['])
+        && (name == "ifdefined" || !name.starts_with("ifdef"))
+        && name != "ifundef"
+        && name != "iff"
+}
+
+/// Find the primitive commands affecting indentation in a line, with their
+/// byte indices
+fn find_primitives(line: &str) -> Vec<(usize, Primitive)> {
+    let mut primitives = vec![];
+    if !line.contains('\\') {
+        return primitives;
+    }
+    // Number of following control sequences which are names being defined
+    let mut skip = 0;
+    let mut in_csname = false;
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            i += 1;
+            continue;
+        }
+        let len = bytes[i + 1..]
+            .iter()
+            .take_while(|b| b.is_ascii_alphabetic() || **b == b'@')
+            .count();
+        // Skip control symbols such as `\\` and `\{`
+        if len == 0 {
+            i += 2;
+            continue;
+        }
+        let name = &line[i + 1..i + 1 + len];
+        let rest = line[i + 1 + len..].trim_start();
+        match name {
+            "csname" => in_csname = true,
+            "endcsname" => in_csname = false,
+            _ => {}
+        }
+        if skip > 0 {
+            // A name built with `\csname` counts as one control sequence
+            if !in_csname {
+                skip -= 1;
+            }
+        } else if DEFINITIONS.contains(&name) {
+            skip = if name == "let" { 2 } else { 1 };
+        } else if PRIMITIVE_OPENS.contains(&name) || is_conditional(name, rest)
+        {
+            primitives.push((i, Primitive::Open));
+        } else if PRIMITIVE_CLOSES.contains(&name) {
+            primitives.push((i, Primitive::Close));
+        } else if name == "else" || name == "or" {
+            primitives.push((i, Primitive::Branch));
+        }
+        i += 1 + len;
+    }
+    primitives
+}
+
 /// Information on the indentation state of a line
 #[derive(Debug, Clone)]
 pub struct Indent {
@@ -34,26 +128,26 @@ impl Indent {
 }
 
 /// Calculate total indentation change due to the current line
-fn get_diff(line: &str, pattern: &Pattern) -> i8 {
+fn get_diff(line: &str, pattern: &Pattern, args: &Args) -> i8 {
     // list environments get double indents
     let mut diff: i8 = 0;
 
     // other environments get single indents
     if pattern.contains_env_begin && line.contains(ENV_BEGIN) {
-        // documents get no global indentation
-        if line.contains(DOC_BEGIN) {
+        // no-indent environments get no global indentation
+        if args.no_indent_envs_begin.iter().any(|r| line.contains(r)) {
             return 0;
-        };
+        }
         diff += 1;
-        diff += i8::from(LISTS_BEGIN.iter().any(|r| line.contains(r)));
+        diff += i8::from(args.lists_begin.iter().any(|r| line.contains(r)));
     } else if pattern.contains_env_end && line.contains(ENV_END) {
-        // documents get no global indentation
-        if line.contains(DOC_END) {
+        // no-indent environments get no global indentation
+        if args.no_indent_envs_end.iter().any(|r| line.contains(r)) {
             return 0;
-        };
+        }
         diff -= 1;
-        diff -= i8::from(LISTS_END.iter().any(|r| line.contains(r)));
-    };
+        diff -= i8::from(args.lists_end.iter().any(|r| line.contains(r)));
+    }
 
     // indent for delimiters
     diff += line
@@ -61,11 +155,19 @@ fn get_diff(line: &str, pattern: &Pattern) -> i8 {
         .map(|x| i8::from(OPENS.contains(&x)) - i8::from(CLOSES.contains(&x)))
         .sum::<i8>();
 
+    // indent for conditionals and groups
+    diff += find_primitives(line)
+        .iter()
+        .map(|(_, p)| {
+            i8::from(*p == Primitive::Open) - i8::from(*p == Primitive::Close)
+        })
+        .sum::<i8>();
+
     diff
 }
 
 /// Calculate dedentation for the current line
-fn get_back(line: &str, pattern: &Pattern, state: &State) -> i8 {
+fn get_back(line: &str, pattern: &Pattern, state: &State, args: &Args) -> i8 {
     // Only need to dedent if indentation is present
     if state.indent.actual == 0 {
         return 0;
@@ -73,26 +175,34 @@ fn get_back(line: &str, pattern: &Pattern, state: &State) -> i8 {
     let mut back: i8 = 0;
 
     if pattern.contains_env_end && line.contains(ENV_END) {
-        // documents get no global indentation
-        if line.contains(DOC_END) {
+        // no-indent environments get no global indentation
+        if args.no_indent_envs_end.iter().any(|r| line.contains(r)) {
             return 0;
-        };
+        }
         // list environments get double indents for indenting items
-        for r in LISTS_END.iter() {
+        for r in &args.lists_end {
             if line.contains(r) {
                 return 2;
-            };
+            }
         }
         // other environments get single indents
         back = 1;
     } else if pattern.contains_item && line.contains(ITEM) {
         // deindent items to make the rest of item environment appear indented
         back += 1;
-    };
+    }
 
-    // Dedent delimiters
+    // Dedent delimiters, conditionals and groups, with branches such as
+    // `\else` closing one block and opening another
+    let primitives = find_primitives(line);
+    let mut primitives = primitives.iter().peekable();
     let mut cumul: i8 = back;
-    for c in line.chars() {
+    for (i, c) in line.char_indices() {
+        if let Some((_, p)) = primitives.next_if(|(j, _)| *j == i) {
+            cumul += i8::from(*p != Primitive::Open);
+            back = max(cumul, back);
+            cumul -= i8::from(*p != Primitive::Close);
+        }
         cumul -= i8::from(OPENS.contains(&c));
         cumul += i8::from(CLOSES.contains(&c));
         back = max(cumul, back);
@@ -107,9 +217,10 @@ fn get_indent(
     prev_indent: &Indent,
     pattern: &Pattern,
     state: &State,
+    args: &Args,
 ) -> Indent {
-    let diff = get_diff(line, pattern);
-    let back = get_back(line, pattern, state);
+    let diff = get_diff(line, pattern, args);
+    let back = get_back(line, pattern, state, args);
     let actual = prev_indent.actual + diff;
     let visual = prev_indent.actual - back;
     Indent { actual, visual }
@@ -131,7 +242,8 @@ pub fn calculate_indent(
     // (if there is one) to ignore diffs from characters in there.
     let comment_index = find_comment_index(line);
     let line_strip = remove_comment(line, comment_index);
-    let mut indent = get_indent(line_strip, &state.indent, pattern, state);
+    let mut indent =
+        get_indent(line_strip, &state.indent, pattern, state, args);
 
     // Record the indent to the logs.
     if args.verbosity == LevelFilter::Trace {
@@ -0,0 +1,270 @@
+//! tex-fmt
+//! An extremely fast LaTeX formatter written in Rust
+//!
+//! The [`Formatter`] type provides a stable interface for formatting
+//! LaTeX source text from other Rust programs.
+//!
+//! ```
+//! use tex_fmt::{FormatOptions, Formatter};
+//!
+//! let mut options = FormatOptions::default();
+//! options.tabsize = 4;
+//! let formatter = Formatter::new(&options);
+//! let output = formatter.format("\\begin{itemize}\n\\item A\n\\end{itemize}\n");
+//! assert_eq!(output.text, "\\begin{itemize}\n    \\item A\n\\end{itemize}\n");
+//! ```
+
+#![warn(missing_docs)]
+#![warn(clippy::nursery)]
+#![warn(clippy::cargo)]
+#![warn(clippy::missing_docs_in_private_items)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::wildcard_imports)]
+#![allow(clippy::multiple_crate_versions)]
+#![allow(clippy::struct_excessive_bools)]
+#![allow(clippy::module_name_repetitions)]
+
+mod align;
+mod args;
+mod bib;
+mod cli;
+mod comments;
+mod config;
+mod format;
+mod git;
+mod ignore;
+mod indent;
+mod logging;
+mod lsp;
+mod ranges;
+mod read;
+mod regexes;
+mod subs;
+mod verbatim;
+mod verify;
+mod wrap;
+mod write;
+
+pub use crate::args::{LineEnding, TabChar, WrapMode};
+pub use crate::ranges::LineRange;
+
+use crate::args::*;
+use crate::format::*;
+use crate::logging::*;
+use crate::regexes::SPLITTING;
+use log::{Level, LevelFilter};
+use merge::Merge;
+
+#[cfg(test)]
+mod tests;
+
+#[cfg(target_family = "unix")]
+/// Native line ending for unix
+const LINE_END: &str = "\n";
+
+#[cfg(target_family = "windows")]
+/// Native line ending for Windows
+const LINE_END: &str = "\r\n";
+
+/// File name used in diagnostics when none is given
+const DEFAULT_FILE: &str = "<input>";
+
+/// Options controlling how LaTeX source text is formatted
+///
+/// New options may be added in future versions, so construct this with
+/// [`FormatOptions::default`] and then set the fields to change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FormatOptions {
+    /// Wrap long lines
+    pub wrap: bool,
+    /// Maximum allowed line length
+    pub wraplen: u8,
+    /// Where to break lines when wrapping
+    pub wrap_mode: WrapMode,
+    /// Join lines which were split in the middle of a sentence
+    pub join_sentences: bool,
+    /// Prefer wrapping outside of braces, brackets and inline math
+    pub brace_wrap: bool,
+    /// Align the columns of tables and aligned environments
+    pub align_tables: bool,
+    /// Sort bibliography entries by key
+    pub bib_sort: bool,
+    /// Order of fields in bibliography entries
+    pub bib_field_order: Vec<String>,
+    /// Number of characters to use as tab size
+    pub tabsize: u8,
+    /// Characters to use for indentation
+    pub tabchar: TabChar,
+    /// Line ending used in formatted text
+    pub line_ending: LineEnding,
+    /// Most verbose level of diagnostics to record
+    pub verbosity: LevelFilter,
+    /// Names of verbatim environments
+    pub verbatims: Vec<String>,
+    /// Names of list environments
+    pub lists: Vec<String>,
+    /// Names of environments which do not indent their contents
+    pub no_indent_envs: Vec<String>,
+    /// Names of commands which begin on a new line
+    pub splitting: Vec<String>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self::from(&Args::default())
+    }
+}
+
+impl From<&Args> for FormatOptions {
+    fn from(args: &Args) -> Self {
+        Self {
+            wrap: args.wrap,
+            wraplen: args.wraplen,
+            wrap_mode: args.wrap_mode,
+            join_sentences: args.join_sentences,
+            brace_wrap: args.brace_wrap,
+            align_tables: args.align_tables,
+            bib_sort: args.bib_sort,
+            bib_field_order: args.bib_field_order.clone(),
+            tabsize: args.tabsize,
+            tabchar: args.tabchar.clone(),
+            line_ending: args.line_ending,
+            verbosity: args.verbosity,
+            verbatims: args.verbatims.clone(),
+            lists: args.lists.clone(),
+            no_indent_envs: args.no_indent_envs.clone(),
+            splitting: args.splitting.clone(),
+        }
+    }
+}
+
+impl From<&FormatOptions> for Args {
+    fn from(options: &FormatOptions) -> Self {
+        let mut args = OptionArgs {
+            wrap: Some(options.wrap),
+            wraplen: Some(options.wraplen),
+            wrapmin: Some(get_wrapmin(options.wraplen)),
+            wrap_mode: Some(options.wrap_mode),
+            join_sentences: Some(options.join_sentences),
+            brace_wrap: Some(options.brace_wrap),
+            align_tables: Some(options.align_tables),
+            bib_sort: Some(options.bib_sort),
+            bib_field_order: options.bib_field_order.clone(),
+            tabsize: Some(options.tabsize),
+            tabchar: Some(options.tabchar.clone()),
+            line_ending: Some(options.line_ending),
+            verbosity: Some(options.verbosity),
+            verbatims: options.verbatims.clone(),
+            verbatims_replace: Some(true),
+            lists: options.lists.clone(),
+            lists_replace: Some(true),
+            no_indent_envs: options.no_indent_envs.clone(),
+            no_indent_envs_replace: Some(true),
+            splitting: options.splitting.clone(),
+            no_splitting: SPLITTING
+                .iter()
+                .map(|(n, _)| (*n).to_string())
+                .filter(|n| !options.splitting.contains(n))
+                .collect(),
+            ..OptionArgs::new()
+        };
+        args.merge(OptionArgs::default());
+        Self::from(args)
+    }
+}
+
+/// Diagnostic recorded while formatting
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    /// Severity of the diagnostic
+    pub level: Level,
+    /// File name associated with the diagnostic
+    pub file: String,
+    /// Line number in the formatted text
+    pub linum_new: Option<usize>,
+    /// Line number in the original text
+    pub linum_old: Option<usize>,
+    /// Content of the line
+    pub line: Option<String>,
+    /// Description of the diagnostic
+    pub message: String,
+}
+
+impl From<&Log> for Diagnostic {
+    fn from(log: &Log) -> Self {
+        Self {
+            level: log.level,
+            file: log.file.clone(),
+            linum_new: log.linum_new,
+            linum_old: log.linum_old,
+            line: log.line.clone(),
+            message: log.message.clone(),
+        }
+    }
+}
+
+/// Result of formatting a piece of LaTeX source text
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct FormatOutput {
+    /// Formatted text
+    pub text: String,
+    /// Diagnostics recorded while formatting
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Formatter for LaTeX source text
+#[derive(Debug)]
+pub struct Formatter {
+    /// Arguments used to drive the formatting functions
+    args: Args,
+}
+
+impl Formatter {
+    /// Construct a new formatter from formatting options
+    #[must_use]
+    pub fn new(options: &FormatOptions) -> Self {
+        Self {
+            args: options.into(),
+        }
+    }
+
+    /// Format source text
+    #[must_use]
+    pub fn format(&self, text: &str) -> FormatOutput {
+        self.format_file(text, DEFAULT_FILE)
+    }
+
+    /// Format source text, using `file` as the name in diagnostics
+    #[must_use]
+    pub fn format_file(&self, text: &str, file: &str) -> FormatOutput {
+        let mut logs = Vec::<Log>::new();
+        let text = format_file(text, file, &self.args, &mut logs);
+        dedup_logs(&mut logs);
+        let diagnostics = logs.iter().map(Diagnostic::from).collect();
+        FormatOutput { text, diagnostics }
+    }
+}
+
+/// Run tex-fmt from the command line, returning its exit code
+///
+/// This reads the command line arguments and may exit the process early,
+/// for example when `--help` is passed.
+#[must_use]
+pub fn main_cli() -> u8 {
+    let mut logs = Vec::<Log>::new();
+    let mut args = get_args(&mut logs);
+    init_logger(args.verbosity);
+
+    let mut statuses = Vec::<FileStatus>::new();
+    let mut exit_code = args.resolve(&mut logs);
+
+    if exit_code == 0 {
+        exit_code = run(&args, &mut logs, &mut statuses);
+    }
+
+    print_logs(&mut logs, &statuses, &args);
+    exit_code
+}
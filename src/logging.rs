@@ -1,11 +1,14 @@
 //! Utilities for logging
 
+use crate::args::*;
 use colored::{Color, Colorize};
 use env_logger::Builder;
 use log::Level;
 use log::Level::{Debug, Error, Info, Trace, Warn};
 use log::LevelFilter;
+use serde_json::{json, Value};
 use std::cmp::Reverse;
+use std::fmt;
 use std::io::Write;
 use std::path::Path;
 use std::time::Instant;
@@ -29,6 +32,60 @@ pub struct Log {
     pub message: String,
 }
 
+/// Outcome of formatting a file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(missing_docs)]
+#[allow(clippy::missing_docs_in_private_items)]
+pub enum Status {
+    Changed,
+    Unchanged,
+    Error,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Changed => write!(f, "changed"),
+            Self::Unchanged => write!(f, "unchanged"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Holds the outcome of formatting a file
+#[derive(Clone, Debug)]
+pub struct FileStatus {
+    /// File name
+    pub file: String,
+    /// Outcome of formatting the file
+    pub status: Status,
+}
+
+impl FileStatus {
+    /// Determine the status of a file from its original and formatted text
+    #[must_use]
+    pub fn new(file: &str, text: &str, new_text: &str) -> Self {
+        let status = if text == new_text {
+            Status::Unchanged
+        } else {
+            Status::Changed
+        };
+        Self {
+            file: file.to_string(),
+            status,
+        }
+    }
+
+    /// Record that a file could not be formatted
+    #[must_use]
+    pub fn error(file: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            status: Status::Error,
+        }
+    }
+}
+
 /// Append a log to the logs list
 fn record_log(
     logs: &mut Vec<Log>,
@@ -96,7 +153,8 @@ const fn get_log_color(log_level: Level) -> Color {
 /// Start the logger
 pub fn init_logger(level_filter: LevelFilter) {
     Builder::new()
-        .filter_level(level_filter)
+        .filter_level(LevelFilter::Off)
+        .filter_module("tex_fmt", level_filter)
         .format(|buf, record| {
             writeln!(
                 buf,
@@ -112,18 +170,23 @@ pub fn init_logger(level_filter: LevelFilter) {
         .init();
 }
 
-/// Display all of the logs collected
-pub fn print_logs(logs: &mut Vec<Log>) {
-    logs.sort_by_key(|l| {
+/// Remove duplicate logs, keeping them in the order they were recorded
+///
+/// Logs are ordered by their position in the list rather than their time,
+/// so that logs from files formatted in parallel stay grouped by file.
+pub fn dedup_logs(logs: &mut Vec<Log>) {
+    let mut indexed: Vec<(usize, Log)> = logs.drain(..).enumerate().collect();
+    indexed.sort_by_key(|(i, l)| {
         (
             l.level,
+            l.file.clone(),
             l.linum_new,
             l.linum_old,
             l.message.clone(),
-            Reverse(l.time),
+            Reverse(*i),
         )
     });
-    logs.dedup_by(|a, b| {
+    indexed.dedup_by(|(_, a), (_, b)| {
         (
             a.level,
             &a.file,
@@ -140,8 +203,174 @@ pub fn print_logs(logs: &mut Vec<Log>) {
             &b.message,
         )
     });
-    logs.sort_by_key(|l| l.time);
+    indexed.sort_by_key(|(i, _)| *i);
+    logs.extend(indexed.into_iter().map(|(_, l)| l));
+}
+
+/// Display all of the logs collected, in the requested output format
+pub fn print_logs(logs: &mut Vec<Log>, statuses: &[FileStatus], args: &Args) {
+    dedup_logs(logs);
+    let shown: Vec<&Log> =
+        logs.iter().filter(|l| l.level <= args.verbosity).collect();
+    let output = match args.output_format {
+        OutputFormat::Human => {
+            print_logs_human(logs);
+            return;
+        }
+        OutputFormat::Json => get_json(&shown, statuses),
+        OutputFormat::JsonLines => get_json_lines(&shown, statuses),
+        OutputFormat::Github => get_github(&shown),
+        OutputFormat::Sarif => get_sarif(&shown),
+    };
+    if output.is_empty() {
+        return;
+    }
+    // Use stderr if stdout is already used for formatted text, diffs or lsp
+    if args.print || args.diff != DiffMode::Off || args.lsp {
+        eprintln!("{output}");
+    } else {
+        println!("{output}");
+    }
+}
+
+/// Convert a log to a JSON object
+fn log_to_json(log: &Log) -> Value {
+    json!({
+        "level": log.level.as_str().to_lowercase(),
+        "file": log.file,
+        "linum_new": log.linum_new,
+        "linum_old": log.linum_old,
+        "line": log.line,
+        "message": log.message,
+    })
+}
+
+/// Convert a file status to a JSON object
+fn status_to_json(status: &FileStatus) -> Value {
+    json!({
+        "file": status.file,
+        "status": status.status.to_string(),
+    })
+}
+
+/// Display logs and file statuses as a JSON object
+pub fn get_json(logs: &[&Log], statuses: &[FileStatus]) -> String {
+    let value = json!({
+        "files": statuses.iter().map(status_to_json).collect::<Vec<_>>(),
+        "logs": logs.iter().map(|l| log_to_json(l)).collect::<Vec<_>>(),
+    });
+    serde_json::to_string_pretty(&value).unwrap()
+}
+
+/// Display logs and file statuses as JSON objects, one per line
+pub fn get_json_lines(logs: &[&Log], statuses: &[FileStatus]) -> String {
+    let mut lines: Vec<String> = vec![];
+    for status in statuses {
+        let mut value = status_to_json(status);
+        value["type"] = json!("file");
+        lines.push(value.to_string());
+    }
+    for log in logs {
+        let mut value = log_to_json(log);
+        value["type"] = json!("log");
+        lines.push(value.to_string());
+    }
+    lines.join("\n")
+}
+
+/// Escape data in a GitHub workflow command
+fn escape_github_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a property in a GitHub workflow command
+fn escape_github_property(s: &str) -> String {
+    escape_github_data(s)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+/// Display logs as GitHub workflow commands for annotations
+pub fn get_github(logs: &[&Log]) -> String {
+    let mut lines: Vec<String> = vec![];
+    for log in logs {
+        let command = match log.level {
+            Error => "error",
+            Warn => "warning",
+            Info | Trace | Debug => "notice",
+        };
+        let mut properties = vec!["title=tex-fmt".to_string()];
+        if !log.file.is_empty() {
+            properties
+                .push(format!("file={}", escape_github_property(&log.file)));
+        }
+        if let Some(linum) = log.linum_old {
+            properties.push(format!("line={linum}"));
+        }
+        lines.push(format!(
+            "::{command} {}::{}",
+            properties.join(","),
+            escape_github_data(&log.message)
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Display logs as a SARIF 2.1.0 report
+pub fn get_sarif(logs: &[&Log]) -> String {
+    let results: Vec<Value> = logs
+        .iter()
+        .map(|log| {
+            let level = match log.level {
+                Error => "error",
+                Warn => "warning",
+                Info | Trace | Debug => "note",
+            };
+            let mut location = json!({
+                "physicalLocation": {
+                    "artifactLocation": { "uri": log.file },
+                },
+            });
+            if let Some(linum) = log.linum_old {
+                location["physicalLocation"]["region"] =
+                    json!({ "startLine": linum });
+            }
+            let mut result = json!({
+                "ruleId": "tex-fmt",
+                "level": level,
+                "message": { "text": log.message },
+            });
+            if !log.file.is_empty() {
+                result["locations"] = json!([location]);
+            }
+            result
+        })
+        .collect();
+    let value = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "tex-fmt",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": [{
+                        "id": "tex-fmt",
+                        "shortDescription": { "text": "LaTeX formatting" },
+                    }],
+                },
+            },
+            "results": results,
+        }],
+    });
+    serde_json::to_string_pretty(&value).unwrap()
+}
 
+/// Display logs as coloured text
+fn print_logs_human(logs: &[Log]) {
     for log in logs {
         let linum_new = log
             .linum_new
@@ -163,9 +392,10 @@ pub fn print_logs(logs: &mut Vec<Log>) {
                 "<stdin>" | "" => "<stdin>".blue().bold(),
                 _ => Path::new(&log.file)
                     .file_name()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
+                    .map_or_else(
+                        || log.file.clone(),
+                        |f| f.to_string_lossy().to_string(),
+                    )
                     .blue()
                     .bold(),
             },
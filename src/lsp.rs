@@ -0,0 +1,320 @@
+//! Language server for formatting documents in editors
+
+use crate::args::*;
+use crate::format::*;
+use crate::logging::*;
+use crate::ranges::*;
+use log::Level::{Error, Info, Trace, Warn};
+use lsp_server::{Connection, ErrorCode, Message, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification, PublishDiagnostics,
+};
+use lsp_types::request::{Formatting, RangeFormatting, Request as _};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, DocumentFormattingParams,
+    DocumentRangeFormattingParams, OneOf, Position, PublishDiagnosticsParams,
+    Range, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextEdit, Uri,
+};
+use serde::de::DeserializeOwned;
+use similar::{DiffTag, TextDiff};
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::slice;
+
+/// Error type returned by the language server
+type LspError = Box<dyn StdError + Sync + Send>;
+
+/// Convert a line number to an LSP position at the start of that line
+fn line_start(linum: usize) -> Position {
+    Position::new(u32::try_from(linum).unwrap_or(u32::MAX), 0)
+}
+
+/// Compute line-based edits which transform `old_text` into `new_text`
+pub fn get_text_edits(old_text: &str, new_text: &str) -> Vec<TextEdit> {
+    let diff = TextDiff::from_lines(old_text, new_text);
+    let new_slices = diff.new_slices();
+    diff.ops()
+        .iter()
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .map(|op| TextEdit {
+            range: Range::new(
+                line_start(op.old_range().start),
+                line_start(op.old_range().end),
+            ),
+            new_text: new_slices[op.new_range()].concat(),
+        })
+        .collect()
+}
+
+/// Convert an LSP range to the lines it covers, counting from one
+pub const fn get_line_range(range: &Range) -> LineRange {
+    let start = range.start.line as usize + 1;
+    let mut end = range.end.line as usize + 1;
+    // A range ending at the start of a line does not include that line
+    if range.end.character == 0 && range.end.line > range.start.line {
+        end -= 1;
+    }
+    LineRange::new(start, end)
+}
+
+/// Get the path of a local file from its URI
+fn uri_to_file(uri: &Uri) -> Option<String> {
+    let scheme = uri.scheme()?;
+    if !scheme.as_str().eq_ignore_ascii_case("file") {
+        return None;
+    }
+    let path = uri.path().as_estr().decode().into_string_lossy();
+    Some(path.to_string())
+}
+
+/// Convert a warning or error log into an LSP diagnostic
+///
+/// Logs without a line number are reported on the first line.
+fn log_to_diagnostic(log: &Log) -> Option<Diagnostic> {
+    let severity = match log.level {
+        Error => DiagnosticSeverity::ERROR,
+        Warn => DiagnosticSeverity::WARNING,
+        _ => return None,
+    };
+    let start = line_start(log.linum_old.map_or(0, |i| i - 1));
+    let end = Position::new(start.line, u32::MAX);
+    Some(Diagnostic {
+        range: Range::new(start, end),
+        severity: Some(severity),
+        source: Some("tex-fmt".to_string()),
+        message: log.message.clone(),
+        ..Diagnostic::default()
+    })
+}
+
+/// Extract the parameters of a notification, logging them if they are
+/// invalid
+fn extract_notification<P: DeserializeOwned>(
+    notification: lsp_server::Notification,
+) -> Option<P> {
+    let method = notification.method.clone();
+    notification
+        .extract(&method)
+        .map_err(|e| log::warn!("Ignoring notification {method}: {e}"))
+        .ok()
+}
+
+/// State of the language server
+struct Server {
+    /// Connection to the client
+    connection: Connection,
+    /// Arguments used for formatting
+    args: Args,
+    /// Text of the documents currently open in the client
+    documents: HashMap<String, String>,
+}
+
+impl Server {
+    /// Format the selected lines of a document, or all lines if none are
+    /// selected, returning the original text, formatted text and logs
+    fn format(
+        &self,
+        uri: &Uri,
+        lines: Vec<LineRange>,
+    ) -> Option<(String, String, Vec<Log>)> {
+        let text = self.documents.get(uri.as_str())?;
+        let mut logs = Vec::<Log>::new();
+        // Use the nearest config file of the document, as on the command line
+        let file = uri_to_file(uri);
+        let configs = file
+            .as_ref()
+            .and_then(|f| {
+                self.args
+                    .get_file_config_args(slice::from_ref(f), &mut logs)
+            })
+            .unwrap_or_default();
+        let file_args = file
+            .as_ref()
+            .map_or(&self.args, |f| self.args.for_file(f, &configs));
+        let args = Args {
+            lines,
+            ..file_args.clone()
+        };
+        let new_text = format_file(text, uri.as_str(), &args, &mut logs);
+        dedup_logs(&mut logs);
+        Some((text.clone(), new_text, logs))
+    }
+
+    /// Publish the warnings found when formatting a document
+    fn publish_diagnostics(&self, uri: &Uri) -> Result<(), LspError> {
+        let diagnostics = self
+            .format(uri, vec![])
+            .map(|(_, _, logs)| {
+                logs.iter().filter_map(log_to_diagnostic).collect()
+            })
+            .unwrap_or_default();
+        let params = PublishDiagnosticsParams {
+            uri: uri.clone(),
+            diagnostics,
+            version: None,
+        };
+        let notification = lsp_server::Notification::new(
+            PublishDiagnostics::METHOD.to_string(),
+            params,
+        );
+        self.connection.sender.send(notification.into())?;
+        Ok(())
+    }
+
+    /// Respond to a request from the client
+    ///
+    /// Requests with invalid parameters get an error response, rather than
+    /// stopping the server.
+    fn handle_request(&self, request: Request) -> Result<(), LspError> {
+        let id = request.id.clone();
+        let response = match request.method.as_str() {
+            Formatting::METHOD => request
+                .extract::<DocumentFormattingParams>(Formatting::METHOD)
+                .map(|(id, params)| {
+                    let edits = self
+                        .format(&params.text_document.uri, vec![])
+                        .map(|(old, new, _)| get_text_edits(&old, &new));
+                    Response::new_ok(id, edits)
+                }),
+            RangeFormatting::METHOD => request
+                .extract::<DocumentRangeFormattingParams>(
+                    RangeFormatting::METHOD,
+                )
+                .map(|(id, params)| {
+                    let lines = vec![get_line_range(&params.range)];
+                    let edits = self
+                        .format(&params.text_document.uri, lines)
+                        .map(|(old, new, _)| get_text_edits(&old, &new));
+                    Response::new_ok(id, edits)
+                }),
+            _ => Ok(Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request: {}", request.method),
+            )),
+        };
+        let response = response.unwrap_or_else(|e| {
+            Response::new_err(
+                id,
+                ErrorCode::InvalidParams as i32,
+                e.to_string(),
+            )
+        });
+        self.connection.sender.send(response.into())?;
+        Ok(())
+    }
+
+    /// Update the stored documents after a notification from the client
+    ///
+    /// Notifications with invalid parameters are logged and ignored, as
+    /// they cannot be answered.
+    fn handle_notification(
+        &mut self,
+        notification: lsp_server::Notification,
+    ) -> Result<(), LspError> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Some(params) = extract_notification::<
+                    lsp_types::DidOpenTextDocumentParams,
+                >(notification) else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                self.documents
+                    .insert(uri.to_string(), params.text_document.text);
+                self.publish_diagnostics(&uri)?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let Some(params) = extract_notification::<
+                    lsp_types::DidChangeTextDocumentParams,
+                >(notification) else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                // Full document sync sends the whole text in the last change
+                if let Some(change) = params.content_changes.into_iter().last()
+                {
+                    self.documents.insert(uri.to_string(), change.text);
+                }
+                self.publish_diagnostics(&uri)?;
+            }
+            DidCloseTextDocument::METHOD => {
+                let Some(params) = extract_notification::<
+                    lsp_types::DidCloseTextDocumentParams,
+                >(notification) else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                self.documents.remove(uri.as_str());
+                // Clear the diagnostics of the closed document
+                self.publish_diagnostics(&uri)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handle messages from the client until it shuts down the server
+    fn main_loop(&mut self) -> Result<(), LspError> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    self.handle_request(request)?;
+                }
+                Message::Notification(notification) => {
+                    self.handle_notification(notification)?;
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Start the language server and communicate over stdin and stdout
+fn serve(args: &Args) -> Result<(), LspError> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::FULL,
+        )),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+    let mut server = Server {
+        connection,
+        args: args.clone(),
+        documents: HashMap::new(),
+    };
+    server.main_loop()?;
+    drop(server);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Run tex-fmt as a language server
+pub fn run_lsp(args: &Args, logs: &mut Vec<Log>) -> u8 {
+    record_file_log(logs, Trace, "", "Starting language server.");
+    match serve(args) {
+        Ok(()) => {
+            record_file_log(logs, Info, "", "Language server stopped.");
+            0
+        }
+        Err(e) => {
+            record_file_log(
+                logs,
+                Error,
+                "",
+                &format!("Language server failed: {e}"),
+            );
+            1
+        }
+    }
+}
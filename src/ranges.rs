@@ -0,0 +1,31 @@
+//! Utilities for formatting only selected lines
+
+use std::fmt;
+
+/// Inclusive range of line numbers, counting from one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineRange {
+    /// First line in the range
+    pub start: usize,
+    /// Last line in the range
+    pub end: usize,
+}
+
+impl LineRange {
+    /// Construct a new line range
+    #[must_use]
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+impl fmt::Display for LineRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.start, self.end)
+    }
+}
+
+/// Check if a line number is in any of the ranges
+pub fn contains_line(ranges: &[LineRange], linum: usize) -> bool {
+    ranges.iter().any(|r| r.start <= linum && linum <= r.end)
+}
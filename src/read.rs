@@ -1,10 +1,16 @@
 //! Utilities for reading files
 
+use crate::args::*;
 use crate::logging::*;
 use crate::regexes::*;
-use log::Level::{Error, Trace};
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::WalkBuilder;
+use log::Level::{Error, Trace, Warn};
+use std::collections::HashSet;
+use std::env::current_dir;
 use std::fs;
 use std::io::Read;
+use std::path::Path;
 
 /// Add a missing extension and read the file
 pub fn read(file: &str, logs: &mut Vec<Log>) -> Option<(String, String)> {
@@ -14,7 +20,7 @@ pub fn read(file: &str, logs: &mut Vec<Log>) -> Option<(String, String)> {
     let mut new_file = file.to_owned();
     if !has_ext {
         new_file.push_str(".tex");
-    };
+    }
     if let Ok(text) = fs::read_to_string(&new_file) {
         return Some((new_file, text));
     }
@@ -50,3 +56,78 @@ pub fn read_stdin(logs: &mut Vec<Log>) -> Option<(String, String)> {
         }
     }
 }
+
+/// Build a glob matcher from the include and exclude lists
+fn get_overrides(args: &Args, logs: &mut Vec<Log>) -> Option<Override> {
+    let root = current_dir().unwrap_or_default();
+    let mut builder = OverrideBuilder::new(root);
+    let globs = args
+        .include
+        .iter()
+        .map(ToOwned::to_owned)
+        .chain(args.exclude.iter().map(|g| format!("!{g}")));
+    for glob in globs {
+        if let Err(e) = builder.add(&glob) {
+            record_file_log(logs, Error, "", &format!("Invalid glob: {e}"));
+            return None;
+        }
+    }
+    match builder.build() {
+        Ok(overrides) => Some(overrides),
+        Err(e) => {
+            record_file_log(logs, Error, "", &format!("Invalid glob: {e}"));
+            None
+        }
+    }
+}
+
+/// Find formattable files in a directory, respecting ignore files
+fn walk_dir(
+    dir: &str,
+    overrides: &Override,
+    logs: &mut Vec<Log>,
+) -> Vec<String> {
+    let mut files = vec![];
+    let walker = WalkBuilder::new(dir)
+        .overrides(overrides.clone())
+        .sort_by_file_name(Ord::cmp)
+        .build();
+    for entry in walker {
+        match entry {
+            Ok(entry) => {
+                let is_file = entry.file_type().is_some_and(|t| t.is_file());
+                let path = entry.path().to_string_lossy();
+                if is_file && EXTENSIONS.iter().any(|e| path.ends_with(e)) {
+                    files.push(path.to_string());
+                }
+            }
+            Err(e) => {
+                record_file_log(logs, Warn, dir, &format!("{e}"));
+            }
+        }
+    }
+    files
+}
+
+/// Expand directories into the formattable files they contain
+pub fn find_files(args: &Args, logs: &mut Vec<Log>) -> Option<Vec<String>> {
+    let overrides = get_overrides(args, logs)?;
+    let mut files = vec![];
+    for file in &args.files {
+        if Path::new(file).is_dir() {
+            let dir_files = walk_dir(file, &overrides, logs);
+            record_file_log(
+                logs,
+                Trace,
+                file,
+                &format!("Found {} files in directory.", dir_files.len()),
+            );
+            files.extend(dir_files);
+        } else {
+            files.push(file.clone());
+        }
+    }
+    let mut seen = HashSet::new();
+    files.retain(|f| seen.insert(f.clone()));
+    Some(files)
+}
@@ -1,15 +1,10 @@
 //! Regexes and matching utilities
 
-use crate::LINE_END;
-use lazy_static::lazy_static;
 use regex::Regex;
+use std::sync::LazyLock;
 
 /// Match a LaTeX \item
 pub const ITEM: &str = "\\item";
-/// Match a LaTeX \begin{document}
-pub const DOC_BEGIN: &str = "\\begin{document}";
-/// Match a LaTeX \end{document}
-pub const DOC_END: &str = "\\end{document}";
 /// Match a LaTeX \begin{...}
 pub const ENV_BEGIN: &str = "\\begin{";
 /// Match a LaTeX \end{...}
@@ -17,8 +12,8 @@ pub const ENV_END: &str = "\\end{";
 /// Acceptable LaTeX file extensions
 pub const EXTENSIONS: [&str; 4] = [".tex", ".bib", ".sty", ".cls"];
 
-/// Names of LaTeX list environments
-const LISTS: [&str; 5] = [
+/// Default names of LaTeX list environments
+pub const LISTS: [&str; 5] = [
     "itemize",
     "enumerate",
     "description",
@@ -26,61 +21,86 @@ const LISTS: [&str; 5] = [
     "inventory",
 ];
 
-/// Names of LaTeX verbatim environments
-const VERBATIMS: [&str; 5] =
+/// Default names of LaTeX environments which do not indent their contents
+pub const NO_INDENT_ENVS: [&str; 1] = ["document"];
+
+/// Default names of LaTeX verbatim environments
+pub const VERBATIMS: [&str; 5] =
     ["verbatim", "Verbatim", "lstlisting", "minted", "comment"];
 
-/// Regex matches for sectioning commands
-const SPLITTING: [&str; 6] = [
-    r"\\begin\{",
-    r"\\end\{",
-    r"\\item(?:$|[^a-zA-Z])",
-    r"\\(?:sub){0,2}section\*?\{",
-    r"\\chapter\*?\{",
-    r"\\part\*?\{",
+/// Names of default splitting commands, with regexes matching them
+pub const SPLITTING: [(&str, &str); 6] = [
+    ("begin", r"\\begin\{"),
+    ("end", r"\\end\{"),
+    ("item", r"\\item(?:$|[^a-zA-Z])"),
+    ("section", r"\\(?:sub){0,2}section\*?\{"),
+    ("chapter", r"\\chapter\*?\{"),
+    ("part", r"\\part\*?\{"),
 ];
 
-// Regexes
-lazy_static! {
-    // A static `String` which is a valid regex to match any one of the
-    // [`SPLITTING_COMMANDS`].
-    pub static ref SPLITTING_STRING: String = [
-        "(",
-        SPLITTING.join("|").as_str(),
-        ")"
-    ].concat();
-    pub static ref RE_NEWLINES: Regex =
-        Regex::new(&format!(r"{LINE_END}{LINE_END}({LINE_END})+")).unwrap();
-    pub static ref RE_TRAIL: Regex =
-        Regex::new(&format!(r" +{LINE_END}")).unwrap();
-    pub static ref VERBATIMS_BEGIN: Vec<String> = VERBATIMS
-        .iter()
-        .map(|l| format!("\\begin{{{l}}}"))
-        .collect();
-    pub static ref VERBATIMS_END: Vec<String> =
-        VERBATIMS.iter().map(|l| format!("\\end{{{l}}}")).collect();
-    pub static ref LISTS_BEGIN: Vec<String> =
-        LISTS.iter().map(|l| format!("\\begin{{{l}}}")).collect();
-    pub static ref LISTS_END: Vec<String> =
-        LISTS.iter().map(|l| format!("\\end{{{l}}}")).collect();
-    // Regex that matches splitting commands
-    pub static ref RE_SPLITTING: Regex = Regex::new(
-        SPLITTING_STRING.as_str()
-    )
-    .unwrap();
-    // Matches splitting commands with non-whitespace characters before it.
-    pub static ref RE_SPLITTING_SHARED_LINE: Regex = Regex::new(
-        [r"(:?\S.*?)", "(:?", SPLITTING_STRING.as_str(), ".*)"]
-        .concat().as_str()
-    )
-    .unwrap();
-    // Matches any splitting command with non-whitespace
-    // characters before it, catches the previous text in a group called
-    // "prev" and captures the command itself and the remaining text
-    // in a group called "env".
-    pub static ref RE_SPLITTING_SHARED_LINE_CAPTURE: Regex = Regex::new(
-        [r"(?P<prev>\S.*?)", "(?P<env>", SPLITTING_STRING.as_str(), ".*)"]
-        .concat().as_str()
+/// Match runs of three or more line endings, whether or not they include `\r`
+pub static RE_NEWLINES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:\r?\n){3,}").unwrap());
+/// Match trailing spaces before a line ending
+pub static RE_TRAIL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r" +\r?\n").unwrap());
+/// Match a LaTeX \item but not commands such as \itemsep
+pub static RE_ITEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\\item(?:$|[^a-zA-Z])").unwrap());
+
+/// Get the regex matching a splitting command given its name
+fn get_splitting_regex(name: &str) -> String {
+    SPLITTING.iter().find(|(n, _)| *n == name).map_or_else(
+        || format!(r"\\{}(?:$|[^a-zA-Z])", regex::escape(name)),
+        |(_, r)| (*r).to_string(),
     )
-    .unwrap();
+}
+
+/// Regexes matching splitting commands, constructed at run-time
+#[derive(Clone, Debug)]
+pub struct SplittingRegexes {
+    /// Matches splitting commands
+    pub splitting: Regex,
+    /// Matches splitting commands with non-whitespace characters before it
+    pub shared_line: Regex,
+    /// Matches any splitting command with non-whitespace
+    /// characters before it, catches the previous text in a group called
+    /// "prev" and captures the command itself and the remaining text
+    /// in a group called "env"
+    pub shared_line_capture: Regex,
+}
+
+impl SplittingRegexes {
+    /// Construct regexes matching any one of the named splitting commands
+    pub fn new(names: &[String]) -> Self {
+        // An empty alternation would match everywhere,
+        // so use a class which never matches instead
+        let splitting_string = if names.is_empty() {
+            r"([^\s\S])".to_string()
+        } else {
+            [
+                "(",
+                names
+                    .iter()
+                    .map(|n| get_splitting_regex(n))
+                    .collect::<Vec<String>>()
+                    .join("|")
+                    .as_str(),
+                ")",
+            ]
+            .concat()
+        };
+        Self {
+            splitting: Regex::new(&splitting_string).unwrap(),
+            shared_line: Regex::new(
+                &[r"(:?\S.*?)", "(:?", &splitting_string, ".*)"].concat(),
+            )
+            .unwrap(),
+            shared_line_capture: Regex::new(
+                &[r"(?P<prev>\S.*?)", "(?P<env>", &splitting_string, ".*)"]
+                    .concat(),
+            )
+            .unwrap(),
+        }
+    }
 }
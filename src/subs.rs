@@ -5,13 +5,12 @@ use crate::comments::*;
 use crate::format::*;
 use crate::logging::*;
 use crate::regexes::*;
-use crate::LINE_END;
 use log::Level;
 use log::LevelFilter;
 
 /// Remove multiple line breaks
-pub fn remove_extra_newlines(text: &str) -> String {
-    let double_line_end = format!("{LINE_END}{LINE_END}");
+pub fn remove_extra_newlines(text: &str, line_end: &str) -> String {
+    let double_line_end = line_end.repeat(2);
     RE_NEWLINES.replace_all(text, double_line_end).to_string()
 }
 
@@ -22,23 +21,26 @@ pub fn remove_tabs(text: &str, args: &Args) -> String {
 }
 
 /// Remove trailing spaces from line endings
-pub fn remove_trailing_spaces(text: &str) -> String {
-    RE_TRAIL.replace_all(text, LINE_END).to_string()
+pub fn remove_trailing_spaces(text: &str, line_end: &str) -> String {
+    RE_TRAIL.replace_all(text, line_end).to_string()
 }
 
 /// Check if line contains content which be split onto a new line
-pub fn needs_split(line: &str, pattern: &Pattern) -> bool {
+pub fn needs_split(line: &str, pattern: &Pattern, args: &Args) -> bool {
+    let regexes = &args.splitting_regexes;
+
     // Check if we should format this line and if we've matched an environment.
     let contains_splittable_env =
-        pattern.contains_splitting && RE_SPLITTING_SHARED_LINE.is_match(line);
+        pattern.contains_splitting && regexes.shared_line.is_match(line);
 
     // If we're not ignoring and we've matched an environment ...
     if contains_splittable_env {
         // ... return `true` if the comment index is `None`
         // (which implies the split point must be in text), otherwise
         // compare the index of the comment with the split point.
-        find_comment_index(line).map_or(true, |comment_index| {
-            if RE_SPLITTING_SHARED_LINE_CAPTURE
+        find_comment_index(line).is_none_or(|comment_index| {
+            if regexes
+                .shared_line_capture
                 .captures(line)
                 .unwrap() // Matched split point so no panic.
                 .get(2)
@@ -71,7 +73,11 @@ pub fn split_line<'a>(
     args: &Args,
     logs: &mut Vec<Log>,
 ) -> (&'a str, &'a str) {
-    let captures = RE_SPLITTING_SHARED_LINE_CAPTURE.captures(line).unwrap();
+    let captures = args
+        .splitting_regexes
+        .shared_line_capture
+        .captures(line)
+        .unwrap();
 
     let (line, [prev, rest, _]) = captures.extract();
 
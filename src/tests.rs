@@ -1,17 +1,27 @@
 use crate::args::*;
-use crate::format_file;
-use crate::fs;
+use crate::config::*;
+use crate::format::*;
+use crate::git::*;
 use crate::logging::*;
+use crate::lsp::*;
+use crate::ranges::*;
+use crate::read::*;
+use crate::verify::*;
+use crate::write::*;
+use crate::{FormatOptions, Formatter};
 use colored::Colorize;
+use lsp_types::{Position, Range};
+use merge::Merge;
 use similar::{ChangeTag, TextDiff};
+use std::fs;
+use std::path::Path;
 
-fn test_file(source_file: &str, target_file: &str) -> bool {
-    let args = Args::default();
+fn test_file(source_file: &str, target_file: &str, args: &Args) -> bool {
     let mut logs = Vec::<Log>::new();
     let source_text = fs::read_to_string(source_file).unwrap();
     let target_text = fs::read_to_string(target_file).unwrap();
     let fmt_source_text =
-        format_file(&source_text, source_file, &args, &mut logs);
+        format_file(&source_text, source_file, args, &mut logs);
 
     if fmt_source_text != target_text {
         println!(
@@ -38,7 +48,7 @@ fn test_file(source_file: &str, target_file: &str) -> bool {
                     format!("+ {change}").green().bold(),
                 ),
                 ChangeTag::Equal => {}
-            };
+            }
         }
     }
 
@@ -52,6 +62,32 @@ fn read_files_from_dir(dir: &str) -> Vec<String> {
         .collect()
 }
 
+/// Check the files in `tests/<feature>/source` format to those in
+/// `tests/<feature>/target`, using the options in `tests/<feature>/tex-fmt.toml`
+fn test_feature(feature: &str) {
+    let dir = format!("tests/{feature}");
+    let config = Path::new(&dir).join("tex-fmt.toml");
+    let mut logs = Vec::<Log>::new();
+    let mut options = if config.exists() {
+        read_config(&config, &mut logs).unwrap()
+    } else {
+        OptionArgs::new()
+    };
+    options.merge(OptionArgs::default());
+    let args = Args::from(options);
+    let mut fail = false;
+    for file in read_files_from_dir(&format!("{dir}/source/")) {
+        let source_file = format!("{dir}/source/{file}");
+        let target_file = format!("{dir}/target/{file}");
+        if !test_file(&source_file, &target_file, &args)
+            || !test_file(&target_file, &target_file, &args)
+        {
+            fail = true;
+        }
+    }
+    assert!(!fail, "Some tests failed in {dir}");
+}
+
 #[test]
 fn test_source() {
     let source_files = read_files_from_dir("./tests/source/");
@@ -59,6 +95,7 @@ fn test_source() {
         if !test_file(
             &format!("tests/source/{file}"),
             &format!("tests/target/{file}"),
+            &Args::default(),
         ) {
             panic!("Failed in {file}");
         }
@@ -73,6 +110,7 @@ fn test_target() {
         if !test_file(
             &format!("tests/target/{file}"),
             &format!("tests/target/{file}"),
+            &Args::default(),
         ) {
             fail = true;
         }
@@ -81,7 +119,415 @@ fn test_target() {
 }
 
 #[test]
-#[ignore]
+fn test_idempotence() {
+    let source_files = read_files_from_dir("./tests/source/");
+    let modes = [
+        OptionArgs::default(),
+        OptionArgs {
+            wrap_mode: Some(WrapMode::Sentence),
+            join_sentences: Some(true),
+            ..OptionArgs::default()
+        },
+        OptionArgs {
+            wrap_mode: Some(WrapMode::Reflow),
+            ..OptionArgs::default()
+        },
+    ];
+    let mut logs = Vec::<Log>::new();
+    let mut fail = false;
+    for mode in modes {
+        let args = Args::from(OptionArgs {
+            check_idempotent: Some(true),
+            ..mode
+        });
+        for file in &source_files {
+            let file = format!("tests/source/{file}");
+            let text = fs::read_to_string(&file).unwrap();
+            let new_text = format_file(&text, &file, &args, &mut logs);
+            if process_output(&args, &file, &text, &new_text, &mut logs) != 0 {
+                println!(
+                    "{} {} {:?}",
+                    "fail".red().bold(),
+                    file.yellow().bold(),
+                    args.wrap_mode
+                );
+                fail = true;
+            }
+        }
+    }
+    assert!(!fail, "Some files were not formatted idempotently");
+}
+
+#[test]
+fn test_config_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tex-fmt.toml");
+    let args = OptionArgs {
+        config: Some(path.clone()),
+        ..OptionArgs::new()
+    };
+    let mut logs = Vec::<Log>::new();
+    fs::write(&path, "wraplen = 60\ncolour = true\n").unwrap();
+    let config_args = get_config_args(&args, &mut logs).unwrap();
+    assert_eq!(config_args.wraplen, Some(60));
+    assert_eq!(logs[0].message, "Unknown config key colour at line 2.");
+    fs::write(&path, "wrap = true\ntabchar = \"tabs\"\n").unwrap();
+    assert!(get_config_args(&args, &mut logs).is_none());
+    assert!(logs[1]
+        .message
+        .starts_with("Invalid config for tabchar at line 2"));
+}
+
+#[test]
+fn test_file_configs() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let sub = root.join("sub");
+    fs::create_dir_all(&sub).unwrap();
+    fs::write(root.join("tex-fmt.toml"), "wraplen = 60\ntabsize = 3\n")
+        .unwrap();
+    fs::write(
+        sub.join("tex-fmt.toml"),
+        "extends = \"../tex-fmt.toml\"\ntabsize = 4\n",
+    )
+    .unwrap();
+    let file = sub.join("a.tex").to_string_lossy().to_string();
+    fs::write(&file, "").unwrap();
+    let mut logs = Vec::<Log>::new();
+    let config_args = read_config(&sub.join("tex-fmt.toml"), &mut logs);
+    let config_args = config_args.unwrap();
+    assert_eq!(config_args.wraplen, Some(60));
+    assert_eq!(config_args.tabsize, Some(4));
+    let args = Args::default();
+    let files = vec![file.clone()];
+    let configs = args.get_file_config_args(&files, &mut logs).unwrap();
+    let file_args = args.for_file(&file, &configs);
+    assert_eq!(file_args.wraplen, 60);
+    assert_eq!(file_args.tabsize, 4);
+    assert!(logs.is_empty());
+    // Config files outside of a git repository are not used
+    let repo = root.join("repo");
+    fs::create_dir_all(repo.join(".git")).unwrap();
+    let file = repo.join("b.tex").to_string_lossy().to_string();
+    assert_eq!(find_file_config(&file), None);
+    // Worktrees and submodules have a `.git` file rather than a directory
+    let worktree = root.join("worktree");
+    fs::create_dir_all(&worktree).unwrap();
+    fs::write(worktree.join(".git"), "gitdir: ../repo/.git\n").unwrap();
+    let file = worktree.join("c.tex").to_string_lossy().to_string();
+    assert_eq!(find_file_config(&file), None);
+}
+
+#[test]
+fn test_verbatims() {
+    test_feature("verbatims");
+}
+
+#[test]
+fn test_lists() {
+    test_feature("lists");
+}
+
+#[test]
+fn test_no_indent_envs() {
+    test_feature("no_indent_envs");
+}
+
+#[test]
+fn test_splitting() {
+    test_feature("splitting");
+}
+
+#[test]
+fn test_find_files() {
+    let mut logs = Vec::<Log>::new();
+    let args = Args::from(OptionArgs {
+        files: vec!["tests/source".to_string()],
+        exclude: vec!["*.bib".to_string()],
+        ..OptionArgs::default()
+    });
+    let files = find_files(&args, &mut logs).unwrap();
+    assert!(files.iter().any(|f| f.ends_with("lists.tex")));
+    assert!(files.iter().any(|f| f.ends_with("quiver.sty")));
+    assert!(!files.iter().any(|f| f.ends_with("refs.bib")));
+}
+
+#[test]
+fn test_plain_diff() {
+    let text = "\\begin{a}\nb\n\\end{a}\n";
+    let mut logs = Vec::<Log>::new();
+    let new_text = format_file(text, "f.tex", &Args::default(), &mut logs);
+    let target = "--- f.tex\n+++ f.tex\n@@ -1,3 +1,3 @@\n \\begin{a}\n-b\n+  b\n \\end{a}\n";
+    assert_eq!(get_diff("f.tex", text, &new_text), target);
+    assert_eq!(get_diff("f.tex", text, text), "");
+}
+
+#[test]
+fn test_check_first_differing_line() {
+    let mut logs = Vec::<Log>::new();
+    let args = Args::from(OptionArgs {
+        check: Some(true),
+        ..OptionArgs::default()
+    });
+    let exit_code =
+        process_output(&args, "f.tex", "a\nb\nc\n", "a\nb\n  c\n", &mut logs);
+    assert_eq!(exit_code, 1);
+    assert_eq!(logs[0].linum_old, Some(3));
+    assert_eq!(logs[0].line.as_deref(), Some("c"));
+}
+
+#[test]
+fn test_check_idempotent_with_check() {
+    let mut logs = Vec::<Log>::new();
+    let args = Args::from(OptionArgs {
+        check: Some(true),
+        check_idempotent: Some(true),
+        ..OptionArgs::default()
+    });
+    let text = "\\begin{itemize}\n\\item A\n\\end{itemize}\n";
+    let new_text = format_file(text, "f.tex", &args, &mut logs);
+    let exit_code = process_output(&args, "f.tex", text, &new_text, &mut logs);
+    assert_eq!(exit_code, 1);
+    assert_eq!(logs.last().unwrap().message, "Incorrect formatting.");
+}
+
+#[test]
+fn test_write_error() {
+    let mut logs = Vec::<Log>::new();
+    let args = Args::default();
+    let exit_code =
+        process_output(&args, "missing/f.tex", "a\n", "b\n", &mut logs);
+    assert_eq!(exit_code, 1);
+    assert!(logs[0].message.starts_with("Could not write the file"));
+    let (exit_code, status) =
+        process_file(&args, "missing/f.tex", "a\n", "b\n", &mut logs);
+    assert_eq!(exit_code, 1);
+    assert_eq!(status.status, Status::Error);
+    let args = Args::from(OptionArgs {
+        check: Some(true),
+        ..OptionArgs::default()
+    });
+    let (exit_code, status) =
+        process_file(&args, "f.tex", "a\n", "b\n", &mut logs);
+    assert_eq!(exit_code, 1);
+    assert_eq!(status.status, Status::Changed);
+}
+
+#[test]
+fn test_write_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("f.tex");
+    fs::write(&path, "a\n").unwrap();
+    let file = path.to_string_lossy().to_string();
+    let mut logs = Vec::<Log>::new();
+    let args = Args::from(OptionArgs {
+        backup: Some(".bak".to_string()),
+        ..OptionArgs::default()
+    });
+    assert_eq!(process_output(&args, &file, "a\n", "b\n", &mut logs), 0);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "b\n");
+    let backup = dir.path().join("f.tex.bak");
+    assert_eq!(fs::read_to_string(backup).unwrap(), "a\n");
+    // The temporary file is renamed over the original
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn test_jobs_log_order() {
+    let dir = tempfile::tempdir().unwrap();
+    // Earlier files are longer, so that later files tend to finish first
+    let files: Vec<String> = (0..8)
+        .map(|i| {
+            let path = dir.path().join(format!("{i}.tex"));
+            fs::write(&path, " a\n".repeat(1000 * (8 - i))).unwrap();
+            path.to_string_lossy().to_string()
+        })
+        .collect();
+    let mut logs = Vec::<Log>::new();
+    let mut statuses = Vec::<FileStatus>::new();
+    let args = Args::from(OptionArgs {
+        check: Some(true),
+        noconfig: Some(true),
+        jobs: Some(4),
+        files: files.clone(),
+        ..OptionArgs::default()
+    });
+    assert_eq!(run(&args, &mut logs, &mut statuses), 1);
+    // The logs of each file are kept together, in the order of the files
+    let mut log_files: Vec<&String> = logs.iter().map(|l| &l.file).collect();
+    log_files.dedup();
+    let status_files: Vec<&String> = statuses.iter().map(|s| &s.file).collect();
+    assert_eq!(log_files, files.iter().collect::<Vec<_>>());
+    assert_eq!(status_files, files.iter().collect::<Vec<_>>());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_write_symlink_permissions() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("target.tex");
+    let link = dir.path().join("link.tex");
+    fs::write(&target, "a\n").unwrap();
+    fs::set_permissions(&target, fs::Permissions::from_mode(0o640)).unwrap();
+    symlink(&target, &link).unwrap();
+    let file = link.to_string_lossy().to_string();
+    let mut logs = Vec::<Log>::new();
+    let args = Args::default();
+    assert_eq!(process_output(&args, &file, "a\n", "b\n", &mut logs), 0);
+    assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+    assert_eq!(fs::read_to_string(&target).unwrap(), "b\n");
+    let mode = fs::metadata(&target).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+}
+
+#[test]
+fn test_verify() {
+    let mut logs = Vec::<Log>::new();
+    let text = "a b\\c % d e\n\nf\n";
+    let new_text = "a\n  b\n\\c % d\n% e\n\nf\n";
+    assert!(verify_text("f.tex", text, new_text, &mut logs));
+    assert!(!verify_text("f.tex", text, "ab\\c % d e\n\nf\n", &mut logs));
+    assert!(!verify_text("f.tex", text, "a b\\c % d e\nf\n", &mut logs));
+    assert!(!verify_text(
+        "f.tex",
+        text,
+        "a b\n% c % d e\n\nf\n",
+        &mut logs
+    ));
+    assert!(!verify_text(
+        "f.tex",
+        "\\begin{x}",
+        "\\be gin{x}",
+        &mut logs
+    ));
+    assert!(!verify_text("f.tex", "x % a b\n", "x % a\nb\n", &mut logs));
+    assert!(!verify_text("f.tex", "x % a\nb\n", "x % a b\n", &mut logs));
+    assert!(verify_text("f.tex", "a&b\\\\\n", "a & b \\\\\n", &mut logs));
+    assert!(!verify_text(
+        "f.tex",
+        "\\verb|&|\n",
+        "\\verb| &|\n",
+        &mut logs
+    ));
+    assert_eq!(logs.len(), 7);
+    assert!(verify_text("f.bib", "@a{b}", "@a{c}", &mut logs));
+    assert_eq!(logs.len(), 8);
+    assert_eq!(logs[7].level, log::Level::Warn);
+}
+
+#[test]
+fn test_lsp_text_edits() {
+    let old = "a\n b\nc\n d\n";
+    let new = "a\nb\nc\nd\n";
+    let edits = get_text_edits(old, new);
+    assert_eq!(edits.len(), 2);
+    assert_eq!(edits[0].range.start.line, 1);
+    assert_eq!(edits[0].range.end.line, 2);
+    assert_eq!(edits[0].new_text, "b\n");
+    let range = Range::new(Position::new(1, 0), Position::new(2, 0));
+    assert_eq!(get_line_range(&range), LineRange::new(2, 2));
+    let range = Range::new(Position::new(1, 0), Position::new(2, 1));
+    assert_eq!(get_line_range(&range), LineRange::new(2, 3));
+}
+
+#[test]
+fn test_lines() {
+    let text = "\\begin{itemize}\n\\item A  \n\\item B\t\n\\end{itemize}\n";
+    let mut logs = Vec::<Log>::new();
+    let args = Args::from(OptionArgs {
+        lines: vec![LineRange::new(3, 3)],
+        ..OptionArgs::default()
+    });
+    let new_text = format_file(text, "f.tex", &args, &mut logs);
+    assert_eq!(
+        new_text,
+        "\\begin{itemize}\n\\item A  \n  \\item B\n\\end{itemize}\n"
+    );
+}
+
+#[test]
+fn test_parse_hunk_header() {
+    let range = parse_hunk_header("@@ -3,2 +4,3 @@ \\section{A}");
+    assert_eq!(range, Some(LineRange::new(4, 6)));
+    let range = parse_hunk_header("@@ -3 +4 @@");
+    assert_eq!(range, Some(LineRange::new(4, 4)));
+    assert_eq!(parse_hunk_header("@@ -3,2 +2,0 @@"), None);
+}
+
+#[test]
+fn test_unquote_path() {
+    assert_eq!(unquote_path("b/a b.tex"), "b/a b.tex");
+    assert_eq!(unquote_path("\"b/a\\\"b\\tc.tex\""), "b/a\"b\tc.tex");
+    assert_eq!(unquote_path("\"b/\\303\\251.tex\""), "b/é.tex");
+}
+
+#[test]
+fn test_sentence_wrap() {
+    test_feature("sentence_wrap");
+}
+
+#[test]
+fn test_reflow() {
+    test_feature("reflow");
+}
+
+#[test]
+fn test_brace_wrap() {
+    test_feature("brace_wrap");
+}
+
+#[test]
+fn test_align_tables() {
+    test_feature("align_tables");
+}
+
+#[test]
+fn test_conditionals() {
+    test_feature("conditionals");
+}
+
+#[test]
+fn test_bib() {
+    test_feature("bib");
+}
+
+#[test]
+fn test_bib_duplicate_keys() {
+    let text = "@misc{b, title = {B}}\n@misc{B, title = {C}}\n";
+    let mut logs = Vec::<Log>::new();
+    format_file(text, "refs.bib", &Args::default(), &mut logs);
+    assert!(logs
+        .iter()
+        .any(|l| l.message == "Duplicate bibliography key."));
+}
+
+#[test]
+fn test_bib_field_order_merge() {
+    // A field order from the command line replaces the one in a config file
+    let mut cli_args = OptionArgs {
+        bib_field_order: vec!["title".to_string()],
+        ..OptionArgs::new()
+    };
+    cli_args.merge(OptionArgs {
+        bib_field_order: vec!["year".to_string()],
+        ..OptionArgs::new()
+    });
+    assert_eq!(cli_args.bib_field_order, ["title"]);
+}
+
+#[test]
+fn test_line_ending() {
+    let text = "a  \r\n\r\n\r\n\r\nb\r\n";
+    let mut logs = Vec::<Log>::new();
+    let args = Args::default();
+    let new_text = format_file(text, "f.tex", &args, &mut logs);
+    assert_eq!(new_text, "a\r\n\r\nb\r\n");
+    test_feature("line_ending");
+}
+
+#[test]
+#[ignore = "used for debugging individual test files"]
 fn test_short() {
     let files = vec![
         //"brackets.tex",
@@ -115,9 +561,123 @@ fn test_short() {
         if !test_file(
             &format!("tests/source/{file}"),
             &format!("tests/target/{file}"),
+            &Args::default(),
         ) {
             fail = true;
         }
     }
     assert!(!fail, "Some tests failed");
 }
+
+#[test]
+fn test_formatter_diagnostics() {
+    let formatter = Formatter::new(&FormatOptions::default());
+    let output = formatter.format_file("a\n}\n", "f.tex");
+    assert_eq!(output.text, "a\n}\n");
+    let warning = output
+        .diagnostics
+        .iter()
+        .find(|d| d.level == log::Level::Warn)
+        .unwrap();
+    assert_eq!(warning.file, "f.tex");
+    assert_eq!(warning.linum_old, Some(2));
+    assert_eq!(warning.line.as_deref(), Some("}"));
+    assert_eq!(warning.message, "Indent is negative.");
+}
+
+/// Logs used for testing the output formats
+fn get_test_logs() -> Vec<Log> {
+    let mut logs = Vec::<Log>::new();
+    record_line_log(
+        &mut logs,
+        log::Level::Warn,
+        "dir:a,b.tex",
+        3,
+        2,
+        "x",
+        "50% done\r\nnext: a, b",
+    );
+    record_file_log(&mut logs, log::Level::Error, "", "Failed.");
+    logs
+}
+
+#[test]
+fn test_output_json() {
+    let logs = get_test_logs();
+    let logs: Vec<&Log> = logs.iter().collect();
+    let statuses = [FileStatus::new("a.tex", "a", "b")];
+    let value: serde_json::Value =
+        serde_json::from_str(&get_json(&logs, &statuses)).unwrap();
+    assert_eq!(value["files"][0]["file"], "a.tex");
+    assert_eq!(value["files"][0]["status"], "changed");
+    let log = &value["logs"][0];
+    assert_eq!(log["level"], "warn");
+    assert_eq!(log["file"], "dir:a,b.tex");
+    assert_eq!(log["linum_new"], 3);
+    assert_eq!(log["linum_old"], 2);
+    assert_eq!(log["line"], "x");
+    assert_eq!(log["message"], "50% done\r\nnext: a, b");
+    assert_eq!(value["logs"][1]["linum_old"], serde_json::Value::Null);
+
+    let lines: Vec<serde_json::Value> = get_json_lines(&logs, &statuses)
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["type"], "file");
+    assert_eq!(lines[0]["status"], "changed");
+    assert_eq!(lines[1]["type"], "log");
+    assert_eq!(lines[1]["level"], "warn");
+    assert_eq!(lines[1]["message"], log["message"]);
+    assert_eq!(lines[2]["type"], "log");
+    assert_eq!(lines[2]["level"], "error");
+}
+
+#[test]
+fn test_output_github() {
+    let logs = get_test_logs();
+    let logs: Vec<&Log> = logs.iter().collect();
+    let output = get_github(&logs);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(
+        lines[0],
+        "::warning title=tex-fmt,file=dir%3Aa%2Cb.tex,line=2::\
+         50%25 done%0D%0Anext: a, b"
+    );
+    assert_eq!(lines[1], "::error title=tex-fmt::Failed.");
+}
+
+#[test]
+fn test_output_sarif() {
+    let logs = get_test_logs();
+    let logs: Vec<&Log> = logs.iter().collect();
+    let value: serde_json::Value =
+        serde_json::from_str(&get_sarif(&logs)).unwrap();
+    assert_eq!(value["version"], "2.1.0");
+    let run = &value["runs"][0];
+    assert_eq!(run["tool"]["driver"]["name"], "tex-fmt");
+    assert!(run["tool"]["driver"]["version"].is_string());
+    let results = run["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["level"], "warning");
+    assert_eq!(results[0]["message"]["text"], "50% done\r\nnext: a, b");
+    let location = &results[0]["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "dir:a,b.tex");
+    assert_eq!(location["region"]["startLine"], 2);
+    assert_eq!(results[1]["level"], "error");
+    assert!(results[1].get("locations").is_none());
+}
+
+#[test]
+fn test_since_option_like() {
+    let args = Args::from(OptionArgs {
+        since: Some("--output=changes.txt".to_string()),
+        ..OptionArgs::default()
+    });
+    let mut logs = Vec::<Log>::new();
+    assert!(get_changed_lines(&args, &mut logs).is_none());
+    assert_eq!(
+        logs.last().unwrap().message,
+        "Invalid git revision --output=changes.txt."
+    );
+}
@@ -1,8 +1,8 @@
 //! Utilities for ignoring verbatim environments
 
+use crate::args::*;
 use crate::format::*;
 use crate::logging::*;
-use crate::regexes::*;
 use log::Level::Warn;
 
 /// Information on the verbatim state of a line
@@ -32,8 +32,9 @@ pub fn get_verbatim(
     file: &str,
     warn: bool,
     pattern: &Pattern,
+    args: &Args,
 ) -> Verbatim {
-    let diff = get_verbatim_diff(line, pattern);
+    let diff = get_verbatim_diff(line, pattern, args);
     let actual = state.verbatim.actual + diff;
     let visual = actual > 0 || state.verbatim.actual > 0;
 
@@ -53,13 +54,13 @@ pub fn get_verbatim(
 }
 
 /// Calculate total verbatim depth change
-fn get_verbatim_diff(line: &str, pattern: &Pattern) -> i8 {
+fn get_verbatim_diff(line: &str, pattern: &Pattern, args: &Args) -> i8 {
     if pattern.contains_env_begin
-        && VERBATIMS_BEGIN.iter().any(|r| line.contains(r))
+        && args.verbatims_begin.iter().any(|r| line.contains(r))
     {
         1
     } else if pattern.contains_env_end
-        && VERBATIMS_END.iter().any(|r| line.contains(r))
+        && args.verbatims_end.iter().any(|r| line.contains(r))
     {
         -1
     } else {
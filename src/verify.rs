@@ -0,0 +1,202 @@
+//! Utilities for checking that formatting only changes whitespace
+
+use crate::bib::*;
+use crate::logging::*;
+use crate::wrap::*;
+use log::Level::{Error, Warn};
+
+/// Piece of text compared between the original and formatted text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token {
+    /// Character other than whitespace
+    Char(char),
+    /// Run of whitespace, with the number of line breaks it contains
+    Space(usize),
+}
+
+/// Check if a run of whitespace separates paragraphs
+const fn is_paragraph_break(breaks: usize) -> bool {
+    breaks >= 2
+}
+
+/// Split text into characters and runs of whitespace, with line numbers
+fn tokenize(text: &str) -> Vec<(usize, Token)> {
+    let mut tokens: Vec<(usize, Token)> = vec![];
+    let mut linum = 1;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            let breaks = usize::from(c == '\n');
+            if let Some((_, Token::Space(n))) = tokens.last_mut() {
+                *n += breaks;
+            } else {
+                tokens.push((linum, Token::Space(breaks)));
+            }
+            linum += breaks;
+        } else {
+            tokens.push((linum, Token::Char(c)));
+        }
+    }
+    // Whitespace at the start and end of a file is not significant
+    if let Some((_, Token::Space(_))) = tokens.last() {
+        tokens.pop();
+    }
+    if let Some((_, Token::Space(_))) = tokens.first() {
+        tokens.remove(0);
+    }
+    tokens
+}
+
+/// Find which tokens are inside inline verbatim, such as `\verb|&|`
+fn find_verbatim_tokens(tokens: &[(usize, Token)]) -> Vec<bool> {
+    // Each token is one character of this text
+    let text: String = tokens
+        .iter()
+        .map(|(_, t)| match t {
+            Token::Char(c) => *c,
+            Token::Space(_) => ' ',
+        })
+        .collect();
+    let mut verbatim = vec![false; tokens.len()];
+    let mut verbatim_end = 0;
+    let mut escaped = false;
+    for (k, (i, c)) in text.char_indices().enumerate() {
+        if i < verbatim_end {
+            verbatim[k] = true;
+            continue;
+        }
+        if c == '\\' && !escaped {
+            if let Some(end) = find_inline_verbatim_end(&text[i..]) {
+                verbatim_end = i + end;
+                verbatim[k] = true;
+                continue;
+            }
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    verbatim
+}
+
+/// Find the line numbers of the first difference between the original and
+/// formatted text, other than in whitespace or inserted comment markers
+///
+/// Spaces can only be inserted around the column and row delimiters of
+/// tables outside of inline verbatim, and line breaks only before a
+/// command. Comments must end on the same lines, and a line break inserted
+/// inside a comment must be followed by `%`.
+fn find_difference(text: &str, new_text: &str) -> Option<(usize, usize)> {
+    let old_tokens = tokenize(text);
+    let new_tokens = tokenize(new_text);
+    let old_verbatim = find_verbatim_tokens(&old_tokens);
+    let mut i = 0;
+    let mut j = 0;
+    let mut in_comment = false;
+    let mut escaped = false;
+    let mut needs_marker = false;
+    let mut after_delimiter = false;
+    loop {
+        // Tables are aligned with spaces around `&` and before `\\`
+        let before_delimiter = !escaped
+            && !old_verbatim.get(i).is_some_and(|v| *v)
+            && match old_tokens.get(i) {
+                Some((_, Token::Char('&'))) => true,
+                Some((_, Token::Char('\\'))) => {
+                    matches!(
+                        old_tokens.get(i + 1),
+                        Some((_, Token::Char('\\')))
+                    )
+                }
+                _ => false,
+            };
+        match (old_tokens.get(i), new_tokens.get(j)) {
+            (None, None) => return None,
+            // Comments wrapped onto a new line begin with a comment marker,
+            // which may be followed by a space
+            (Some(&(_, Token::Char(_))), Some(&(_, Token::Char('%'))))
+                if needs_marker =>
+            {
+                needs_marker = false;
+                j += 1;
+                if let Some((_, Token::Space(0))) = new_tokens.get(j) {
+                    j += 1;
+                }
+            }
+            (Some(&(_, Token::Char(a))), Some(&(_, Token::Char(b))))
+                if a == b && !needs_marker =>
+            {
+                in_comment |= a == '%' && !escaped;
+                after_delimiter =
+                    a == '&' && !escaped && !in_comment && !old_verbatim[i];
+                escaped = a == '\\' && !escaped;
+                i += 1;
+                j += 1;
+            }
+            // A comment must end where it ended in the original text
+            (Some(&(_, Token::Space(m))), Some(&(_, Token::Space(n))))
+                if is_paragraph_break(m) == is_paragraph_break(n)
+                    && !(in_comment && m > 0 && n == 0) =>
+            {
+                needs_marker = in_comment && m == 0 && n > 0;
+                in_comment &= m == 0;
+                escaped = false;
+                after_delimiter = false;
+                i += 1;
+                j += 1;
+            }
+            // Line breaks can be added before commands, but not paragraph
+            // breaks
+            (Some(&(_, Token::Char('\\'))), Some(&(_, Token::Space(n))))
+                if n > 0
+                    && !is_paragraph_break(n)
+                    && !in_comment
+                    && !escaped =>
+            {
+                j += 1;
+            }
+            (Some(&(_, Token::Char(_))), Some(&(_, Token::Space(0))))
+                if !in_comment && (before_delimiter || after_delimiter) =>
+            {
+                after_delimiter = false;
+                j += 1;
+            }
+            (old, new) => {
+                let linum_old =
+                    old.or_else(|| old_tokens.last()).map_or(1, |t| t.0);
+                let linum_new =
+                    new.or_else(|| new_tokens.last()).map_or(1, |t| t.0);
+                return Some((linum_old, linum_new));
+            }
+        }
+    }
+}
+
+/// Check that the formatted text only differs from the original in
+/// whitespace and inserted comment markers
+///
+/// Bibliography files are not checked, as their formatting changes
+/// delimiters and the order of entries, and a warning is recorded instead.
+pub fn verify_text(
+    file: &str,
+    text: &str,
+    new_text: &str,
+    logs: &mut Vec<Log>,
+) -> bool {
+    if is_bib_file(file) {
+        let msg = "Verification skipped for bibliography files.";
+        record_file_log(logs, Warn, file, msg);
+        return true;
+    }
+    let Some((linum_old, linum_new)) = find_difference(text, new_text) else {
+        return true;
+    };
+    let line = text.lines().nth(linum_old - 1).unwrap_or_default();
+    record_line_log(
+        logs,
+        Error,
+        file,
+        linum_new,
+        linum_old,
+        line,
+        "Formatting changes the content of the file.",
+    );
+    false
+}
@@ -4,6 +4,7 @@ use crate::args::*;
 use crate::comments::*;
 use crate::format::*;
 use crate::logging::*;
+use crate::regexes::{ENV_BEGIN, ENV_END, RE_ITEM};
 use log::Level;
 use log::LevelFilter;
 
@@ -12,41 +13,388 @@ pub const TEXT_LINE_START: &str = "";
 /// String slice to start wrapped comment lines
 pub const COMMENT_LINE_START: &str = "% ";
 
+/// Abbreviations whose final full stop does not end a sentence
+const ABBREVIATIONS: [&str; 30] = [
+    "al.", "approx.", "cf.", "Ch.", "Chap.", "Def.", "Dr.", "e.g.", "Eq.",
+    "Eqs.", "etc.", "Fig.", "Figs.", "i.e.", "Lem.", "Mr.", "Mrs.", "Ms.",
+    "No.", "p.", "pp.", "Prof.", "Prop.", "Ref.", "Refs.", "resp.", "Sec.",
+    "Tab.", "Thm.", "vs.",
+];
+
+/// Environments whose contents are display math
+const MATH_ENVS: [&str; 9] = [
+    "align",
+    "alignat",
+    "displaymath",
+    "eqnarray",
+    "equation",
+    "flalign",
+    "gather",
+    "math",
+    "multline",
+];
+
+/// Characters which may close a sentence after its punctuation
+const SENTENCE_CLOSING: [char; 5] = [')', ']', '}', '\'', '"'];
+
+/// Check if text ends with a full stop, question mark or exclamation mark
+/// which is not part of an abbreviation
+fn ends_sentence(text: &str) -> bool {
+    let text = text.trim_end().trim_end_matches(SENTENCE_CLOSING);
+    let mut chars = text.chars().rev();
+    let (Some(last), prev) = (chars.next(), chars.next()) else {
+        return false;
+    };
+    if !matches!(last, '.' | '?' | '!') || prev.is_none_or(|c| c == '\\') {
+        return false;
+    }
+    if last != '.' {
+        return true;
+    }
+    // Check the last word for abbreviations and initials
+    let word = text
+        .rsplit([' ', '~', '('])
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches(|c: char| !c.is_alphanumeric());
+    let is_initial = word.len() == 2 && word.starts_with(char::is_uppercase);
+    !is_initial && !ABBREVIATIONS.contains(&word)
+}
+
+/// Find the first space at which a line can be broken after a sentence,
+/// outside of any braces, inline math or inline verbatim
+pub fn find_sentence_break(line: &str) -> Option<usize> {
+    let comment_index = find_comment_index(line).unwrap_or(line.len());
+    let text = &line[..comment_index];
+    let mut depth: i32 = 0;
+    let mut math = false;
+    let mut verbatim_end = 0;
+    let mut prev_char: Option<char> = None;
+    for (i, c) in text.char_indices() {
+        if i < verbatim_end {
+            prev_char = None;
+            continue;
+        }
+        if prev_char != Some('\\') {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                '$' => math = !math,
+                '\\' => {
+                    let rest = &text[i..];
+                    if rest.starts_with("\\(") {
+                        math = true;
+                    } else if rest.starts_with("\\)") {
+                        math = false;
+                    } else if let Some(end) = find_inline_verbatim_end(rest) {
+                        verbatim_end = i + end;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if c == ' ' && depth == 0 && !math && ends_sentence(&text[..i]) {
+            let next_char = text[i..].trim_start().chars().next();
+            if next_char.is_some_and(|n| !n.is_lowercase()) {
+                return Some(i);
+            }
+        }
+        prev_char = Some(c);
+    }
+    None
+}
+
+/// Check if text begins with the name of a display math environment
+fn starts_with_math_env(text: &str) -> bool {
+    text.split_once('}')
+        .is_some_and(|(env, _)| MATH_ENVS.contains(&env.trim_end_matches('*')))
+}
+
+/// Determine whether display math is open at the end of a line
+pub fn get_display_math(line: &str, display_math: bool) -> bool {
+    let line = remove_comment(line, find_comment_index(line));
+    let bytes = line.as_bytes();
+    let mut display_math = display_math;
+    let mut i = 0;
+    while i < bytes.len() {
+        // Slice only at ASCII characters, which are always char boundaries
+        match bytes[i] {
+            b'\\' => {
+                let rest = &line[i..];
+                if rest.starts_with("\\[") {
+                    display_math = true;
+                } else if rest.starts_with("\\]") {
+                    display_math = false;
+                } else if let Some(env) = rest.strip_prefix(ENV_BEGIN) {
+                    display_math |= starts_with_math_env(env);
+                } else if let Some(env) = rest.strip_prefix(ENV_END) {
+                    display_math &= !starts_with_math_env(env);
+                }
+                // Skip the escaped character
+                i += 2;
+            }
+            b'$' if line[i..].starts_with("$$") => {
+                display_math = !display_math;
+                i += 2;
+            }
+            _ => i += 1,
+        }
+    }
+    display_math
+}
+
+/// Check if every brace or bracket opened in a line is closed in the same
+/// line
+fn has_balanced_braces(line: &str) -> bool {
+    let mut braces: i32 = 0;
+    let mut brackets: i32 = 0;
+    let mut prev_char: Option<char> = None;
+    for c in line.chars() {
+        if prev_char != Some('\\') {
+            match c {
+                '{' => braces += 1,
+                '}' => braces -= 1,
+                '[' => brackets += 1,
+                ']' => brackets -= 1,
+                _ => {}
+            }
+        }
+        if braces < 0 || brackets < 0 {
+            return false;
+        }
+        prev_char = Some(c);
+    }
+    braces == 0 && brackets == 0
+}
+
+/// Check if a line could be part of a paragraph of text, ignoring any
+/// comment at its end
+fn is_paragraph_line(line: &str, args: &Args) -> bool {
+    let line = remove_comment(line, find_comment_index(line)).trim();
+    !line.is_empty()
+        && has_balanced_braces(line)
+        && !(line.contains('\\')
+            && (line.contains(ENV_BEGIN)
+                || line.contains(ENV_END)
+                || line.contains("\\\\")
+                || line.contains("\\[")
+                || line.contains("\\]")
+                || RE_ITEM.is_match(line)
+                || args.splitting_regexes.splitting.is_match(line)))
+        && !line.contains("$$")
+        && !line
+            .match_indices('&')
+            .any(|(i, _)| !line[..i].ends_with('\\'))
+}
+
+/// Check if a line begins with a command such as `\label` or `\def`,
+/// rather than with text of a paragraph
+fn starts_with_command(line: &str) -> bool {
+    line.trim_start()
+        .strip_prefix('\\')
+        .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_alphabetic()))
+}
+
+/// Get the first sentence of a line, as broken by the sentence wrap mode
+fn first_sentence(line: &str) -> &str {
+    find_sentence_break(line).map_or(line, |p| &line[..p])
+}
+
+/// Get the last sentence of a line, as broken by the sentence wrap mode
+fn last_sentence(line: &str) -> &str {
+    let mut rest = line;
+    while let Some(p) = find_sentence_break(rest) {
+        rest = &rest[p + 1..];
+    }
+    rest
+}
+
+/// Check if the next line of a paragraph should be joined onto a line
+///
+/// In sentence mode, only the sentences which would end up adjacent are
+/// checked, so that breaking the joined line after each sentence gives a
+/// stable result.
+pub fn needs_join(
+    line: &str,
+    next_line: &str,
+    state: &State,
+    file: &str,
+    args: &Args,
+) -> bool {
+    // Package and class files are not prose
+    let is_prose = ![".cls", ".sty"].iter().any(|e| file.ends_with(e));
+    if !is_prose
+        || !args.wrap
+        || state.verbatim.actual > 0
+        || state.ignore.actual
+    {
+        return false;
+    }
+    let (end, start) = match args.wrap_mode {
+        WrapMode::Width => return false,
+        WrapMode::Sentence if !args.join_sentences || ends_sentence(line) => {
+            return false;
+        }
+        WrapMode::Sentence => (last_sentence(line), first_sentence(next_line)),
+        WrapMode::Reflow => (line, next_line),
+    };
+    !starts_with_command(end)
+        && !starts_with_command(start)
+        && find_comment_index(end).is_none()
+        && !start.contains("tex-fmt:")
+        && is_paragraph_line(end, args)
+        && is_paragraph_line(start, args)
+        && !get_display_math(line, state.display_math)
+}
+
+/// Join the next line of a paragraph onto a line
+pub fn join_lines(line: &str, next_line: &str) -> String {
+    [line.trim_end(), next_line.trim_start()].join(" ")
+}
+
 /// Check if a line needs wrapping
 pub fn needs_wrap(line: &str, indent_length: usize, args: &Args) -> bool {
     args.wrap && (line.chars().count() + indent_length > args.wraplen.into())
 }
 
+/// Find the end of a `\verb` or `\lstinline` command at the start of some
+/// text, whose contents cannot be broken across lines
+pub fn find_inline_verbatim_end(text: &str) -> Option<usize> {
+    let rest = if let Some(rest) = text.strip_prefix("\\verb") {
+        rest.strip_prefix('*').unwrap_or(rest)
+    } else {
+        let rest = text.strip_prefix("\\lstinline")?;
+        match rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+            Some((_, r)) => r,
+            None => rest,
+        }
+    };
+    let delim = rest.chars().next().filter(|c| !c.is_alphabetic())?;
+    let close = if delim == '{' { '}' } else { delim };
+    let start = text.len() - rest.len() + delim.len_utf8();
+    Some(
+        text[start..]
+            .find(close)
+            .map_or(text.len(), |e| start + e + close.len_utf8()),
+    )
+}
+
+/// Pick a wrap point from candidate `(index, width)` pairs, taking the last
+/// one before the wrap boundary or else the first one after it
+fn pick_wrap_point(
+    points: impl Iterator<Item = (usize, usize)>,
+    wrap_boundary: usize,
+) -> Option<(usize, usize)> {
+    let mut wrap_point = None;
+    for (i, width) in points {
+        if width > wrap_boundary && wrap_point.is_some() {
+            break;
+        }
+        wrap_point = Some((i, width));
+    }
+    wrap_point
+}
+
 /// Find the best place to break a long line
+///
+/// With `--brace-wrap`, points outside of any braces, brackets or inline math
+/// are preferred, and points inside inline math or verbatim are never used.
 fn find_wrap_point(
     line: &str,
     indent_length: usize,
     args: &Args,
 ) -> Option<usize> {
-    let mut wrap_point: Option<usize> = None;
+    // Each candidate is (byte index, width, outside of any group)
+    let mut points: Vec<(usize, usize, bool)> = vec![];
     let mut after_char = false;
     let mut prev_char: Option<char> = None;
 
-    let mut line_width = 0;
+    let comment_index = find_comment_index(line).unwrap_or(line.len());
+    let mut depth: i32 = 0;
+    let mut math = false;
+    let mut verbatim_end = 0;
+    let mut double_dollar = None;
 
-    let wrap_boundary = usize::from(args.wrapmin) - indent_length;
+    // Joined paragraphs are filled up to the line length, so that wrapping
+    // them again breaks at the same points
+    let wrapmin = if args.wrap_mode == WrapMode::Width {
+        args.wrapmin
+    } else {
+        args.wraplen
+    };
+    let wrap_boundary = usize::from(wrapmin).saturating_sub(indent_length);
 
     // Return *byte* index rather than *char* index.
-    for (i, c) in line.char_indices() {
-        line_width += 1;
-        if line_width > wrap_boundary && wrap_point.is_some() {
-            break;
+    for (line_width, (i, c)) in (1..).zip(line.char_indices()) {
+        let escaped = prev_char == Some('\\');
+        if i >= verbatim_end && i < comment_index && !escaped {
+            match c {
+                '{' | '[' => depth += 1,
+                '}' | ']' => depth = (depth - 1).max(0),
+                // The second dollar of `$$` is already counted
+                '$' if double_dollar == Some(i) => {}
+                '$' => {
+                    math = !math;
+                    if line[i + 1..].starts_with('$') {
+                        double_dollar = Some(i + 1);
+                    }
+                }
+                '\\' => {
+                    let rest = &line[i..];
+                    if rest.starts_with("\\(") || rest.starts_with("\\[") {
+                        math = true;
+                    } else if rest.starts_with("\\)") || rest.starts_with("\\]")
+                    {
+                        math = false;
+                    } else if let Some(end) = find_inline_verbatim_end(rest) {
+                        verbatim_end = i + end;
+                    }
+                }
+                _ => {}
+            }
         }
-        if c == ' ' && prev_char != Some('\\') {
-            if after_char {
-                wrap_point = Some(i);
+        if c == ' ' && !escaped {
+            let grouped =
+                i < comment_index && (i < verbatim_end || math || depth > 0);
+            let protected = i < verbatim_end || (math && i < comment_index);
+            if after_char && !(args.brace_wrap && protected) {
+                points.push((i, line_width, !grouped));
             }
         } else if c != '%' {
             after_char = true;
         }
         prev_char = Some(c);
     }
-    wrap_point
+
+    let all_points = points.iter().map(|&(i, w, _)| (i, w));
+    if !args.brace_wrap {
+        return pick_wrap_point(all_points, wrap_boundary).map(|(i, _)| i);
+    }
+
+    // Fall back to points inside braces only if no other point fits
+    let max_width = usize::from(args.wraplen).saturating_sub(indent_length);
+    let outside_points = points.iter().filter(|p| p.2).map(|&(i, w, _)| (i, w));
+    let outside = pick_wrap_point(outside_points, wrap_boundary);
+    outside
+        .filter(|&(_, w)| w <= max_width)
+        .or_else(|| pick_wrap_point(all_points, wrap_boundary))
+        .or(outside)
+        .map(|(i, _)| i)
+}
+
+/// Break a line after the end of its first sentence
+///
+/// Lines for which `sentence_line` is false, such as rows of tables, are
+/// never broken.
+pub fn apply_sentence_wrap<'a>(
+    line: &'a str,
+    sentence_line: bool,
+    args: &Args,
+) -> Option<[&'a str; 2]> {
+    if !args.wrap || args.wrap_mode != WrapMode::Sentence || !sentence_line {
+        return None;
+    }
+    find_sentence_break(line).map(|p| [&line[0..p], &line[p + 1..]])
 }
 
 /// Wrap a long line into a short prefix and a suffix
@@ -85,7 +433,7 @@ pub fn apply_wrap<'a>(
                 "Line cannot be wrapped.",
             );
         }
-    };
+    }
 
     wrap_point.map(|p| {
         let this_line = &line[0..p];
@@ -1,15 +1,141 @@
 //! Utilities for writing formatted files
 
 use crate::args::*;
-use crate::fs;
+use crate::format::*;
 use crate::logging::*;
+use crate::verify::*;
+use colored::Colorize;
 use log::Level::Error;
-use std::path;
+use similar::TextDiff;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Suffix of the temporary file written before replacing a file
+const TEMP_SUFFIX: &str = ".tex-fmt.tmp";
+
+/// Append a suffix to the name of a file
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Write text to a new file with the given permissions
+fn write_temp_file(
+    path: &Path,
+    text: &str,
+    permissions: fs::Permissions,
+) -> io::Result<()> {
+    let mut temp_file = fs::File::create(path)?;
+    temp_file.write_all(text.as_bytes())?;
+    temp_file.sync_all()?;
+    fs::set_permissions(path, permissions)
+}
+
+/// Message recorded for files which are not correctly formatted
+const INCORRECT: &str = "Incorrect formatting.";
 
 /// Write a formatted file to disk
-fn write_file(file: &str, text: &str) {
-    let filepath = path::Path::new(&file).canonicalize().unwrap();
-    fs::write(filepath, text).expect("Could not write the file");
+///
+/// The text is written to a temporary file which then replaces the
+/// original, so that a failed write never leaves a truncated file.
+fn write_file(file: &str, text: &str, args: &Args) -> io::Result<()> {
+    // Replace the target of a symlink rather than the link itself
+    let path = Path::new(file).canonicalize()?;
+    let permissions = fs::metadata(&path)?.permissions();
+    if let Some(suffix) = &args.backup {
+        fs::copy(&path, with_suffix(&path, suffix))?;
+    }
+    let temp_path = with_suffix(&path, TEMP_SUFFIX);
+    let result = write_temp_file(&temp_path, text, permissions)
+        .and_then(|()| fs::rename(&temp_path, &path));
+    if result.is_err() {
+        fs::remove_file(&temp_path).ok();
+    }
+    result
+}
+
+/// Get a unified diff between the original and formatted text
+pub fn get_diff(file: &str, text: &str, new_text: &str) -> String {
+    let diff = TextDiff::from_lines(text, new_text);
+    diff.unified_diff().header(file, file).to_string()
+}
+
+/// Print a unified diff between the original and formatted text
+fn print_diff(file: &str, text: &str, new_text: &str, mode: DiffMode) {
+    let unified = get_diff(file, text, new_text);
+    if mode == DiffMode::Plain {
+        print!("{unified}");
+        return;
+    }
+    for line in unified.split_inclusive('\n') {
+        let (content, end) = line
+            .strip_suffix('\n')
+            .map_or((line, ""), |content| (content, "\n"));
+        let colored_line =
+            if content.starts_with("---") || content.starts_with("+++") {
+                content.bold()
+            } else if content.starts_with("@@") {
+                content.cyan()
+            } else if content.starts_with('-') {
+                content.red()
+            } else if content.starts_with('+') {
+                content.green()
+            } else {
+                content.normal()
+            };
+        print!("{colored_line}{end}");
+    }
+}
+
+/// Record an error at the first line which differs between two texts
+fn record_first_difference(
+    logs: &mut Vec<Log>,
+    file: &str,
+    text: &str,
+    new_text: &str,
+    message: &str,
+) {
+    let mut old_lines = text.lines();
+    let mut new_lines = new_text.lines();
+    let mut linum = 1;
+    loop {
+        match (old_lines.next(), new_lines.next()) {
+            (Some(old_line), Some(new_line)) if old_line == new_line => {
+                linum += 1;
+            }
+            (old_line, _) => {
+                let line = old_line.unwrap_or_default();
+                record_line_log(logs, Error, file, linum, linum, line, message);
+                return;
+            }
+        }
+    }
+}
+
+/// Check that formatting a file a second time makes no further changes
+fn check_idempotent(
+    args: &Args,
+    file: &str,
+    new_text: &str,
+    logs: &mut Vec<Log>,
+) -> bool {
+    // Logs from the second pass repeat those from the first
+    let mut second_logs = Vec::<Log>::new();
+    let second_text = format_file(new_text, file, args, &mut second_logs);
+    if second_text == new_text {
+        return true;
+    }
+    record_first_difference(
+        logs,
+        file,
+        new_text,
+        &second_text,
+        "Formatting is not idempotent.",
+    );
+    false
 }
 
 /// Handle the newly formatted file
@@ -20,13 +146,56 @@ pub fn process_output(
     new_text: &str,
     logs: &mut Vec<Log>,
 ) -> u8 {
+    // Keep the original text if formatting would change its content
+    if args.verify && !verify_text(file, text, new_text, logs) {
+        if args.print {
+            print!("{text}");
+        }
+        return 1;
+    }
+    let mut exit_code = u8::from(
+        args.check_idempotent && !check_idempotent(args, file, new_text, logs),
+    );
     if args.print {
         print!("{}", &new_text);
+    } else if args.diff != DiffMode::Off && text != new_text {
+        print_diff(file, text, new_text, args.diff);
+        record_first_difference(logs, file, text, new_text, INCORRECT);
+        exit_code = 1;
     } else if args.check && text != new_text {
-        record_file_log(logs, Error, file, "Incorrect formatting.");
-        return 1;
-    } else if text != new_text {
-        write_file(file, new_text);
+        record_first_difference(logs, file, text, new_text, INCORRECT);
+        exit_code = 1;
+    } else if text != new_text && !args.check_idempotent {
+        if let Err(e) = write_file(file, new_text, args) {
+            let msg = format!("Could not write the file: {e}");
+            record_file_log(logs, Error, file, &msg);
+            exit_code = 1;
+        }
     }
-    0
+    exit_code
+}
+
+/// Handle the newly formatted file, returning its exit code and status
+///
+/// Incorrect formatting found by `--check` or `--diff` leaves the status
+/// as changed, but any other failure, such as a file which could not be
+/// written, gives an error status.
+pub fn process_file(
+    args: &Args,
+    file: &str,
+    text: &str,
+    new_text: &str,
+    logs: &mut Vec<Log>,
+) -> (u8, FileStatus) {
+    let start = logs.len();
+    let exit_code = process_output(args, file, text, new_text, logs);
+    let failed = logs[start..]
+        .iter()
+        .any(|log| log.level == Error && log.message != INCORRECT);
+    let status = if failed {
+        FileStatus::error(file)
+    } else {
+        FileStatus::new(file, text, new_text)
+    };
+    (exit_code, status)
 }